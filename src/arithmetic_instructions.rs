@@ -11,7 +11,7 @@ pub fn add_instruction(state: &mut State, source_register: Register) {
 // #[cfg_attr(test, mutate)]
 pub fn add_mem_instruction(state: &mut State) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    let memory_value = state.memory[memory_address as usize];
+    let memory_value = state.read_memory(memory_address);
     adi_instruction(state, memory_value);
 }
 
@@ -32,7 +32,7 @@ pub fn adc_instruction(state: &mut State, source_register: Register) {
 // #[cfg_attr(test, mutate)]
 pub fn adc_mem_instruction(state: &mut State) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    let memory_value = state.memory[memory_address as usize];
+    let memory_value = state.read_memory(memory_address);
     aci_instruction(state, memory_value);
 }
 
@@ -57,7 +57,7 @@ pub fn sub_instruction(state: &mut State, source_register: Register) {
 // #[cfg_attr(test, mutate)]
 pub fn sub_mem_instruction(state: &mut State) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    let memory_value = state.memory[memory_address as usize];
+    let memory_value = state.read_memory(memory_address);
     sui_instruction(state, memory_value);
 }
 
@@ -78,7 +78,7 @@ pub fn sbb_instruction(state: &mut State, source_register: Register) {
 // #[cfg_attr(test, mutate)]
 pub fn sbb_mem_instruction(state: &mut State) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    let memory_value = state.memory[memory_address as usize];
+    let memory_value = state.read_memory(memory_address);
     sbi_instruction(state, memory_value);
 }
 
@@ -106,9 +106,9 @@ pub fn inr_instruction(state: &mut State, register: Register) {
 // #[cfg_attr(test, mutate)]
 pub fn inr_mem_instruction(state: &mut State) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    let memory_value = state.memory[memory_address as usize];
+    let memory_value = state.read_memory(memory_address);
     let new_memory_value = memory_value.wrapping_add(1);
-    state.memory[memory_address as usize] = new_memory_value;
+    state.write_memory(memory_address, new_memory_value);
     state.set_condition_flags_from_result(new_memory_value);
     state.condition_flags[ConditionFlag::AuxiliaryCarry] =
         bit_operations::calculate_auxiliary_carry(memory_value, 1, false);
@@ -124,9 +124,9 @@ pub fn dcr_instruction(state: &mut State, register: Register) {
 // #[cfg_attr(test, mutate)]
 pub fn dcr_mem_instruction(state: &mut State) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    let memory_value = state.memory[memory_address as usize];
+    let memory_value = state.read_memory(memory_address);
     let new_memory_value = memory_value.wrapping_sub(1);
-    state.memory[memory_address as usize] = new_memory_value;
+    state.write_memory(memory_address, new_memory_value);
     state.set_condition_flags_from_result(new_memory_value);
     state.condition_flags[ConditionFlag::AuxiliaryCarry] =
         bit_operations::calculate_auxiliary_carry(memory_value, 1, true);
@@ -0,0 +1,689 @@
+use std::collections::HashMap;
+
+use crate::InstructionDataType;
+use crate::disassembler;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AssemblerError {
+    pub line_number: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: {}", self.line_number, self.message)
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+#[derive(Copy, Clone)]
+struct OpCodeInfo {
+    op_code: u8,
+    data_type: InstructionDataType,
+}
+
+// Maps the text of an instruction without its data operand (e.g. "MVI A" or "JNZ")
+// to its opcode, by running every opcode through the disassembler.
+fn op_code_table() -> HashMap<String, OpCodeInfo> {
+    let mut op_code_table = HashMap::new();
+
+    for op_code in 0..=u8::MAX {
+        let operation = disassembler::disassemble_op_code(op_code);
+        let key = match disassembler::operands(&operation) {
+            Some(operands) => format!("{} {operands}", disassembler::mnemonic(&operation)),
+            None => disassembler::mnemonic(&operation),
+        };
+
+        // The undocumented aliases always come after the documented opcodes, so they are never used
+        op_code_table.entry(key).or_insert(OpCodeInfo {
+            op_code,
+            data_type: operation.additional_data_required(),
+        });
+    }
+
+    op_code_table
+}
+
+struct Line {
+    line_number: usize,
+    label: Option<String>,
+    mnemonic: Option<String>,
+    operands: Vec<String>,
+}
+
+enum Statement<'a> {
+    Instruction(OpCodeInfo, Option<&'a str>),
+    Org(&'a str),
+    Db(&'a [String]),
+    Dw(&'a [String]),
+    Ds(&'a str),
+    Equ(&'a str),
+    End,
+}
+
+struct Assembler {
+    op_code_table: HashMap<String, OpCodeInfo>,
+    symbols: HashMap<String, i64>,
+    address: u16,
+    statement_address: u16,
+    image: Vec<u8>,
+}
+
+// Assembles Intel 8080 source into a memory image starting at address 0000h,
+// so that it can be passed straight to State::load_memory.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| parse_line(index + 1, text))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut assembler = Assembler {
+        op_code_table: op_code_table(),
+        symbols: HashMap::new(),
+        address: 0x0000,
+        statement_address: 0x0000,
+        image: Vec::new(),
+    };
+
+    assembler.run_pass(&lines, false)?;
+    assembler.address = 0x0000;
+    assembler.run_pass(&lines, true)?;
+
+    Ok(assembler.image)
+}
+
+impl Assembler {
+    fn run_pass(&mut self, lines: &[Line], is_emitting: bool) -> Result<(), AssemblerError> {
+        for line in lines {
+            let error = |message: String| AssemblerError {
+                line_number: line.line_number,
+                message,
+            };
+
+            self.statement_address = self.address;
+            let statement = self.statement(line).map_err(error)?;
+
+            if let Some(label) = &line.label {
+                let value = match &statement {
+                    Some(Statement::Equ(expression)) => {
+                        self.evaluate(expression, true).map_err(error)?
+                    }
+                    _ => i64::from(self.address),
+                };
+
+                if !is_emitting && self.symbols.insert(label.clone(), value).is_some() {
+                    return Err(error(format!("Duplicate definition of symbol {label}")));
+                }
+            }
+
+            let Some(statement) = statement else {
+                continue;
+            };
+
+            match statement {
+                Statement::Instruction(op_code_info, data_operand) => {
+                    let op_code = op_code_info.op_code;
+                    let data_bytes = match (op_code_info.data_type, data_operand) {
+                        (InstructionDataType::Single, Some(operand)) => {
+                            vec![self.byte_value(operand, is_emitting).map_err(error)?]
+                        }
+                        (InstructionDataType::LowHigh, Some(operand)) => self
+                            .word_value(operand, is_emitting)
+                            .map_err(error)?
+                            .to_le_bytes()
+                            .to_vec(),
+                        _ => Vec::new(),
+                    };
+                    self.emit(&[&[op_code], &data_bytes[..]].concat(), is_emitting)
+                        .map_err(error)?;
+                }
+                Statement::Org(expression) => {
+                    self.address = self.word_value(expression, true).map_err(error)?;
+                }
+                Statement::Db(operands) => {
+                    for operand in operands {
+                        let bytes = match string_literal(operand) {
+                            Some(text) if text.chars().count() != 1 => text.bytes().collect(),
+                            _ => vec![self.byte_value(operand, is_emitting).map_err(error)?],
+                        };
+                        self.emit(&bytes, is_emitting).map_err(error)?;
+                    }
+                }
+                Statement::Dw(operands) => {
+                    for operand in operands {
+                        let word = self.word_value(operand, is_emitting).map_err(error)?;
+                        self.emit(&word.to_le_bytes(), is_emitting).map_err(error)?;
+                    }
+                }
+                Statement::Ds(expression) => {
+                    let size = self.word_value(expression, true).map_err(error)?;
+                    self.emit(&vec![0; usize::from(size)], is_emitting)
+                        .map_err(error)?;
+                }
+                Statement::Equ(_) => {}
+                Statement::End => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn statement<'a>(&self, line: &'a Line) -> Result<Option<Statement<'a>>, String> {
+        let Some(mnemonic) = &line.mnemonic else {
+            return Ok(None);
+        };
+
+        let single_operand = || match &line.operands[..] {
+            [operand] => Ok(operand.as_str()),
+            _ => Err(format!("{mnemonic} requires a single operand")),
+        };
+
+        let statement = match mnemonic.as_str() {
+            "ORG" => Statement::Org(single_operand()?),
+            "DB" => Statement::Db(&line.operands),
+            "DW" => Statement::Dw(&line.operands),
+            "DS" => Statement::Ds(single_operand()?),
+            "EQU" => {
+                if line.label.is_none() {
+                    return Err("EQU requires a symbol name".to_string());
+                }
+                Statement::Equ(single_operand()?)
+            }
+            "END" => Statement::End,
+            _ => self.instruction_statement(mnemonic, &line.operands)?,
+        };
+
+        if matches!(statement, Statement::Db(_) | Statement::Dw(_)) && line.operands.is_empty() {
+            return Err(format!("{mnemonic} requires at least one operand"));
+        }
+
+        Ok(Some(statement))
+    }
+
+    fn instruction_statement<'a>(
+        &self,
+        mnemonic: &str,
+        operands: &'a [String],
+    ) -> Result<Statement<'a>, String> {
+        let key = |operands: &[String]| {
+            if operands.is_empty() {
+                mnemonic.to_string()
+            } else {
+                format!("{mnemonic} {}", operands.join(","))
+            }
+        };
+
+        if let Some(op_code_info) = self
+            .op_code_table
+            .get(&key(&register_operands_of(operands)))
+            && op_code_info.data_type == InstructionDataType::None
+        {
+            return Ok(Statement::Instruction(*op_code_info, None));
+        }
+
+        if let Some((data_operand, register_operands)) = operands.split_last()
+            && let Some(op_code_info) = self
+                .op_code_table
+                .get(&key(&register_operands_of(register_operands)))
+            && op_code_info.data_type != InstructionDataType::None
+        {
+            return Ok(Statement::Instruction(*op_code_info, Some(data_operand)));
+        }
+
+        if self
+            .op_code_table
+            .keys()
+            .any(|key| key.split(' ').next() == Some(mnemonic))
+        {
+            Err(format!("Invalid operands for {mnemonic}"))
+        } else {
+            Err(format!("Unknown instruction {mnemonic}"))
+        }
+    }
+
+    fn emit(&mut self, bytes: &[u8], is_emitting: bool) -> Result<(), String> {
+        let end_address = usize::from(self.address) + bytes.len();
+
+        if end_address > usize::from(u16::MAX) + 1 {
+            return Err("Output goes past the end of memory".to_string());
+        }
+
+        if is_emitting {
+            if self.image.len() < end_address {
+                self.image.resize(end_address, 0);
+            }
+            self.image[usize::from(self.address)..end_address].copy_from_slice(bytes);
+        }
+
+        self.address = self.address.wrapping_add(bytes.len() as u16);
+        Ok(())
+    }
+
+    fn byte_value(&self, expression: &str, is_resolving: bool) -> Result<u8, String> {
+        let value = self.evaluate(expression, is_resolving)?;
+
+        if (-128..=255).contains(&value) {
+            Ok(value as u8)
+        } else {
+            Err(format!("Value {value} does not fit in a byte"))
+        }
+    }
+
+    fn word_value(&self, expression: &str, is_resolving: bool) -> Result<u16, String> {
+        let value = self.evaluate(expression, is_resolving)?;
+
+        if (-32768..=65535).contains(&value) {
+            Ok(value as u16)
+        } else {
+            Err(format!("Value {value} does not fit in a word"))
+        }
+    }
+
+    // Symbols are only resolved when needed, so that forward references work in the first pass
+    fn evaluate(&self, expression: &str, is_resolving: bool) -> Result<i64, String> {
+        let tokens = tokenize(expression)?;
+        let mut parser = ExpressionParser {
+            tokens: &tokens,
+            position: 0,
+            assembler: self,
+            is_resolving,
+        };
+        let value = parser.expression()?;
+
+        if parser.position != tokens.len() {
+            return Err(format!("Invalid expression {expression}"));
+        }
+
+        Ok(value)
+    }
+}
+
+fn register_operands_of(operands: &[String]) -> Vec<String> {
+    operands
+        .iter()
+        .map(|operand| operand.to_ascii_uppercase())
+        .collect()
+}
+
+fn string_literal(operand: &str) -> Option<String> {
+    let quote = operand.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+
+    if operand.len() < 2 || !operand.ends_with(quote) {
+        return None;
+    }
+
+    let doubled_quote = format!("{quote}{quote}");
+    Some(operand[1..operand.len() - 1].replace(&doubled_quote, &quote.to_string()))
+}
+
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, ';') => return &text[..index],
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+    }
+
+    text
+}
+
+fn split_operands(text: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut current_operand = String::new();
+    let mut quote = None;
+
+    for c in text.chars() {
+        match (quote, c) {
+            (None, ',') => {
+                operands.push(current_operand.trim().to_string());
+                current_operand.clear();
+                continue;
+            }
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+        current_operand.push(c);
+    }
+
+    if !current_operand.trim().is_empty() || !operands.is_empty() {
+        operands.push(current_operand.trim().to_string());
+    }
+
+    operands
+}
+
+fn is_symbol_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '?' || c == '@'
+}
+
+fn is_symbol_part(c: char) -> bool {
+    is_symbol_start(c) || c.is_ascii_digit()
+}
+
+fn is_symbol(text: &str) -> bool {
+    text.starts_with(is_symbol_start) && text.chars().all(is_symbol_part)
+}
+
+fn parse_line(line_number: usize, text: &str) -> Result<Line, AssemblerError> {
+    let error = |message: String| AssemblerError {
+        line_number,
+        message,
+    };
+    let mut text = strip_comment(text).trim();
+    let mut label = None;
+
+    let first_word_end = text
+        .find(|c: char| c.is_whitespace() || c == ':')
+        .unwrap_or(text.len());
+    let first_word = &text[..first_word_end];
+
+    if text[first_word_end..].starts_with(':') {
+        if !is_symbol(first_word) {
+            return Err(error(format!("Invalid label {first_word}")));
+        }
+        label = Some(first_word.to_ascii_uppercase());
+        text = text[first_word_end + 1..].trim();
+    } else {
+        let rest = text[first_word_end..].trim_start();
+        let second_word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+
+        if rest[..second_word_end].eq_ignore_ascii_case("EQU") {
+            if !is_symbol(first_word) {
+                return Err(error(format!("Invalid symbol name {first_word}")));
+            }
+            label = Some(first_word.to_ascii_uppercase());
+            text = rest;
+        }
+    }
+
+    if text.is_empty() {
+        return Ok(Line {
+            line_number,
+            label,
+            mnemonic: None,
+            operands: Vec::new(),
+        });
+    }
+
+    let mnemonic_end = text.find(char::is_whitespace).unwrap_or(text.len());
+
+    Ok(Line {
+        line_number,
+        label,
+        mnemonic: Some(text[..mnemonic_end].to_ascii_uppercase()),
+        operands: split_operands(&text[mnemonic_end..]),
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Symbol(String),
+    CurrentAddress,
+    Operator(char),
+}
+
+fn parse_number(text: &str) -> Result<i64, String> {
+    let upper_text = text.to_ascii_uppercase();
+    let (digits, radix) = match upper_text.chars().last() {
+        Some('H') => (&upper_text[..upper_text.len() - 1], 16),
+        Some('B') => (&upper_text[..upper_text.len() - 1], 2),
+        Some('O' | 'Q') => (&upper_text[..upper_text.len() - 1], 8),
+        Some('D') => (&upper_text[..upper_text.len() - 1], 10),
+        _ => (upper_text.as_str(), 10),
+    };
+
+    i64::from_str_radix(digits, radix).map_err(|_| format!("Invalid number {text}"))
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = expression.chars().collect();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+
+        if c.is_whitespace() {
+            index += 1;
+        } else if c.is_ascii_digit() {
+            let start = index;
+            while index < chars.len() && chars[index].is_ascii_alphanumeric() {
+                index += 1;
+            }
+            let text: String = chars[start..index].iter().collect();
+            tokens.push(Token::Number(parse_number(&text)?));
+        } else if is_symbol_start(c) {
+            let start = index;
+            while index < chars.len() && is_symbol_part(chars[index]) {
+                index += 1;
+            }
+            let text: String = chars[start..index].iter().collect();
+            tokens.push(Token::Symbol(text.to_ascii_uppercase()));
+        } else if c == '\'' || c == '"' {
+            let end = chars[index + 1..]
+                .iter()
+                .position(|d| *d == c)
+                .ok_or_else(|| format!("Unterminated character in {expression}"))?;
+            let text: String = chars[index + 1..index + 1 + end].iter().collect();
+            let mut text_chars = text.chars();
+            match (text_chars.next(), text_chars.next()) {
+                (Some(character), None) if character.is_ascii() => {
+                    tokens.push(Token::Number(character as i64));
+                }
+                _ => return Err(format!("Invalid character {c}{text}{c}")),
+            }
+            index += end + 2;
+        } else if c == '$' {
+            tokens.push(Token::CurrentAddress);
+            index += 1;
+        } else if "+-*/()".contains(c) {
+            tokens.push(Token::Operator(c));
+            index += 1;
+        } else {
+            return Err(format!("Unexpected character {c} in {expression}"));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct ExpressionParser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    assembler: &'a Assembler,
+    is_resolving: bool,
+}
+
+impl ExpressionParser<'_> {
+    fn next_operator_is(&mut self, operator: char) -> bool {
+        if self.tokens.get(self.position) == Some(&Token::Operator(operator)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expression(&mut self) -> Result<i64, String> {
+        let mut value = self.term()?;
+
+        loop {
+            if self.next_operator_is('+') {
+                value = value.wrapping_add(self.term()?);
+            } else if self.next_operator_is('-') {
+                value = value.wrapping_sub(self.term()?);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<i64, String> {
+        let mut value = self.factor()?;
+
+        loop {
+            if self.next_operator_is('*') {
+                value = value.wrapping_mul(self.factor()?);
+            } else if self.next_operator_is('/') {
+                let divisor = self.factor()?;
+                if divisor == 0 {
+                    if self.is_resolving {
+                        return Err("Division by zero".to_string());
+                    }
+                    value = 0;
+                } else {
+                    value /= divisor;
+                }
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Result<i64, String> {
+        if self.next_operator_is('-') {
+            return Ok(-self.factor()?);
+        }
+
+        if self.next_operator_is('+') {
+            return self.factor();
+        }
+
+        if self.next_operator_is('(') {
+            let value = self.expression()?;
+            if !self.next_operator_is(')') {
+                return Err("Missing closing parenthesis".to_string());
+            }
+            return Ok(value);
+        }
+
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or_else(|| "Missing operand in expression".to_string())?;
+        self.position += 1;
+
+        match token {
+            Token::Number(value) => Ok(*value),
+            Token::CurrentAddress => Ok(i64::from(self.assembler.statement_address)),
+            Token::Symbol(name) => match self.assembler.symbols.get(name) {
+                Some(value) => Ok(*value),
+                None if !self.is_resolving => Ok(0),
+                None => Err(format!("Undefined symbol {name}")),
+            },
+            Token::Operator(operator) => Err(format!("Unexpected {operator} in expression")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::disassemble_instruction;
+
+    fn assert_assembles_to(source: &str, expected_bytes: &[u8]) {
+        assert_eq!(assemble(source), Ok(expected_bytes.to_vec()));
+    }
+
+    fn assert_assembly_error(source: &str, line_number: usize, message: &str) {
+        assert_eq!(
+            assemble(source),
+            Err(AssemblerError {
+                line_number,
+                message: message.to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn assemble_handles_instructions_with_and_without_data() {
+        assert_assembles_to(
+            "DCR B\nMOV M,B\nINX H\nJMP 0000h",
+            &[0x05, 0x70, 0x23, 0xC3, 0x00, 0x00],
+        );
+        assert_assembles_to(
+            "mvi a, 3eh\nlxi sp,0F000H\nout 6",
+            &[0x3E, 0x3E, 0x31, 0x00, 0xF0, 0xD3, 0x06],
+        );
+    }
+
+    #[test]
+    fn assemble_resolves_forward_and_backward_labels() {
+        assert_assembles_to(
+            "START: JMP END_LOOP ; Skip ahead\n\
+             LOOP:  DCR B\n\
+             END_LOOP:\n\
+                    JNZ LOOP\n\
+                    JMP START",
+            &[0xC3, 0x04, 0x00, 0x05, 0xC2, 0x03, 0x00, 0xC3, 0x00, 0x00],
+        );
+    }
+
+    #[test]
+    fn assemble_handles_directives() {
+        assert_assembles_to(
+            "COUNT EQU 2 + 1\n\
+             ORG 4\n\
+             DB COUNT, 'Hi', 0FFh, -1\n\
+             DW TABLE, $\n\
+             DS COUNT\n\
+             TABLE: DB ';'\n\
+             END\n\
+             DB 1",
+            &[
+                0x00, 0x00, 0x00, 0x00, 0x03, 0x48, 0x69, 0xFF, 0xFF, 0x10, 0x00, 0x09, 0x00, 0x00,
+                0x00, 0x00, 0x3B,
+            ],
+        );
+    }
+
+    #[test]
+    fn assemble_evaluates_expressions() {
+        assert_assembles_to(
+            "BASE EQU 2400h\n\
+             LXI H,BASE + 2 * (3 + 1) - 1\n\
+             MVI A,'A' + 1\n\
+             MVI B,1010b / 2\n\
+             MVI C,17o",
+            &[0x21, 0x07, 0x24, 0x3E, 0x42, 0x06, 0x05, 0x0E, 0x0F],
+        );
+    }
+
+    #[test]
+    fn assemble_reports_errors_with_line_numbers() {
+        assert_assembly_error("NOP\nFOO A", 2, "Unknown instruction FOO");
+        assert_assembly_error("MOV A", 1, "Invalid operands for MOV");
+        assert_assembly_error("NOP\n\nJMP NOWHERE", 3, "Undefined symbol NOWHERE");
+        assert_assembly_error("MVI A,256", 1, "Value 256 does not fit in a byte");
+        assert_assembly_error("A: NOP\nA: NOP", 2, "Duplicate definition of symbol A");
+        assert_assembly_error("ORG 0FFFFh\nDW 0", 2, "Output goes past the end of memory");
+        assert_assembly_error("DS LATER\nLATER: NOP", 1, "Undefined symbol LATER");
+        assert_assembly_error("MVI A,(1", 1, "Missing closing parenthesis");
+    }
+
+    #[test]
+    fn assemble_round_trips_with_the_disassembler_for_every_op_code() {
+        for op_code in 0..=u8::MAX {
+            let bytes = [op_code, 0x34, 0x12];
+            let instruction = disassemble_instruction(&bytes, 0x0000, 0x0000).unwrap();
+            let assembled_bytes = assemble(&instruction.to_string()).unwrap();
+            let reassembled_instruction =
+                disassemble_instruction(&assembled_bytes, 0x0000, 0x0000).unwrap();
+
+            assert_eq!(
+                reassembled_instruction.operation, instruction.operation,
+                "Expected {instruction} to assemble back to the same operation",
+            );
+            assert_eq!(reassembled_instruction.data(), instruction.data());
+            assert_eq!(assembled_bytes.len(), instruction.bytes.len());
+        }
+    }
+}
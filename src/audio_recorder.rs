@@ -0,0 +1,93 @@
+use crate::machine::TimedAudioEvent;
+use crate::scheduler::CPU_CLOCK_SPEED;
+use crate::synth::{Mixer, SAMPLE_RATE};
+use crate::wav;
+
+// Mixes the machine's sounds into a recording, placing each at the emulated time it was triggered,
+// so the recording plays back at the right speed even if the emulator ran faster or slower.
+pub struct AudioRecorder {
+    mixer: Mixer,
+    samples: Vec<u8>,
+    // Emulated time recorded so far, which only moves forward
+    recorded_state_count: usize,
+    last_state_count: usize,
+}
+
+impl AudioRecorder {
+    pub fn new(mixer: Mixer, start_state_count: usize) -> Self {
+        AudioRecorder {
+            mixer,
+            samples: Vec::new(),
+            recorded_state_count: 0,
+            last_state_count: start_state_count,
+        }
+    }
+
+    // Records up to the given state count, which is usually the end of the frame the events came
+    // from. If the state count goes backwards, such as after loading a save state, the recording
+    // carries on from where it was.
+    pub fn record(&mut self, events: &[TimedAudioEvent], state_count: usize) {
+        for timed_event in events {
+            self.record_until(timed_event.state_count);
+            self.mixer.handle_event(timed_event.event);
+        }
+        self.record_until(state_count);
+    }
+
+    fn record_until(&mut self, state_count: usize) {
+        self.recorded_state_count += state_count.saturating_sub(self.last_state_count);
+        self.last_state_count = state_count;
+
+        let num_samples = self.recorded_state_count * SAMPLE_RATE as usize / CPU_CLOCK_SPEED;
+        let new_samples = self.mixer.render(num_samples - self.samples.len());
+        self.samples.extend_from_slice(&new_samples);
+    }
+
+    pub fn samples(&self) -> &[u8] {
+        &self.samples
+    }
+
+    pub fn wav_bytes(&self) -> Vec<u8> {
+        wav::encode(&self.samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{AudioEvent, SoundName};
+
+    fn timed_event(state_count: usize, event: AudioEvent) -> TimedAudioEvent {
+        TimedAudioEvent { state_count, event }
+    }
+
+    #[test]
+    fn sounds_start_at_the_sample_for_their_state_count() {
+        let mut mixer = Mixer::default();
+        mixer.set_sound(SoundName::Shoot, vec![0xFF; 4]);
+        let mut recorder = AudioRecorder::new(mixer, 1_000_000);
+
+        // 11025 samples per 2 million states, so 200,000 states is 1102.5 samples
+        recorder.record(
+            &[timed_event(1_200_000, AudioEvent::Play(SoundName::Shoot))],
+            1_400_000,
+        );
+
+        let samples = recorder.samples();
+        assert_eq!(samples.len(), 2_205);
+        assert_eq!(samples[1_101], 0x80);
+        assert_eq!(samples[1_102..1_106], [0xFF; 4]);
+        assert_eq!(samples[1_106], 0x80);
+    }
+
+    #[test]
+    fn recording_carries_on_when_the_state_count_goes_backwards() {
+        let mut recorder = AudioRecorder::new(Mixer::default(), 0);
+        recorder.record(&[], 400_000);
+        recorder.record(&[], 200_000);
+        recorder.record(&[], 300_000);
+
+        assert_eq!(recorder.samples().len(), 2_756);
+        assert_eq!(recorder.wav_bytes().len(), 44 + 2_756);
+    }
+}
@@ -64,7 +64,7 @@ fn assert_stack_pointer_has_value(state: &State, expected_value: u16) {
 }
 
 fn assert_memory_location_contains_value(state: &State, memory_address: u16, expected_value: u8) {
-    let actual_value = state.memory[memory_address as usize];
+    let actual_value = state.memory.read(memory_address);
     assert_eq!(
         actual_value, expected_value,
         "Expected memory location {memory_address:#06X} to have value {expected_value}, but instead it had value {actual_value}",
@@ -95,7 +95,7 @@ pub fn assert_state_is_as_expected(actual_state: &State, expected_state: &State)
     assert_interrupts_enabled_state(actual_state, expected_state.are_interrupts_enabled);
     assert_halted_state(actual_state, expected_state.is_halted);
     for memory_address in 0..MEMORY_SIZE {
-        let expected_value = expected_state.memory[memory_address];
+        let expected_value = expected_state.memory.read(memory_address as u16);
         assert_memory_location_contains_value(actual_state, memory_address as u16, expected_value);
     }
 }
@@ -1,25 +1,37 @@
+use crate::CpuError;
 // #[cfg(test)]
 // use mutagen::mutate;
 
 // #[cfg_attr(test, mutate)]
 pub fn is_bit_set(value: u8, bit_index: u8) -> bool {
+    try_is_bit_set(value, bit_index).unwrap_or_else(|error| panic!("{error}"))
+}
+
+// #[cfg_attr(test, mutate)]
+pub fn try_is_bit_set(value: u8, bit_index: u8) -> Result<bool, CpuError> {
     if bit_index >= 8 {
-        panic!("Invalid bit index of {bit_index}");
+        return Err(CpuError::InvalidBitIndex(bit_index));
     }
 
     let shifted_value = value >> bit_index;
-    shifted_value & 0b0000_0001 != 0
+    Ok(shifted_value & 0b0000_0001 != 0)
 }
 
 // #[cfg_attr(test, mutate)]
 pub fn set_bit_in_value(value: &mut u8, bit_index: u8, bit_flag: bool) {
+    try_set_bit_in_value(value, bit_index, bit_flag).unwrap_or_else(|error| panic!("{error}"));
+}
+
+// #[cfg_attr(test, mutate)]
+pub fn try_set_bit_in_value(value: &mut u8, bit_index: u8, bit_flag: bool) -> Result<(), CpuError> {
     if bit_index >= 8 {
-        panic!("Invalid bit index of {bit_index}");
+        return Err(CpuError::InvalidBitIndex(bit_index));
     }
 
     let bit_mask = 1 << bit_index;
     let bit_value_mask = if bit_flag { bit_mask } else { 0b0000_0000 };
     *value = *value & !bit_mask | bit_value_mask;
+    Ok(())
 }
 
 // #[cfg_attr(test, mutate)]
@@ -103,6 +115,22 @@ mod tests {
         set_bit_in_value(&mut 127, 8, true);
     }
 
+    #[test]
+    fn try_is_bit_set_returns_an_error_when_given_an_invalid_bit_index() {
+        assert_eq!(try_is_bit_set(127, 8), Err(CpuError::InvalidBitIndex(8)));
+        assert_eq!(try_is_bit_set(127, 6), Ok(true));
+    }
+
+    #[test]
+    fn try_set_bit_in_value_returns_an_error_when_given_an_invalid_bit_index() {
+        let mut value = 127;
+        assert_eq!(
+            try_set_bit_in_value(&mut value, 8, false),
+            Err(CpuError::InvalidBitIndex(8))
+        );
+        assert_eq!(value, 127);
+    }
+
     #[test]
     fn reverse_byte_reverses_a_byte() {
         assert_eq!(reverse_byte(0b1011_1000), 0b0001_1101);
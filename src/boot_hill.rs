@@ -0,0 +1,557 @@
+use log::debug;
+use maplit::hashmap;
+
+use crate::machine::{
+    AudioEvent, DipSwitch, DipSwitches, Input, Machine, SoundName, set_in_port_from_flags,
+    shift_new_value_into_data, shift_value,
+};
+use crate::memory::MidwayMemory;
+use crate::rom_set::{RomFile, RomSet};
+use crate::save_state::{SaveStateError, SaveStateReader, SaveStateWriter};
+use crate::{CpuError, Ports, State, bit_operations};
+
+// From MAME's mw8080bw driver
+const DIP_SWITCHES: [DipSwitch; 3] = [
+    DipSwitch {
+        name: "coinage",
+        description: "Coins needed per credit",
+        mask: 0b0000_0011,
+        values: &[
+            ("1_coin_1_credit", 0b00),
+            ("1_coin_2_credits", 0b01),
+            ("2_coins_1_credit", 0b10),
+        ],
+        default_value: "1_coin_1_credit",
+    },
+    DipSwitch {
+        name: "game_time",
+        description: "Length of a game in seconds",
+        mask: 0b0000_1100,
+        values: &[
+            ("60", 0b0000_0000),
+            ("70", 0b0000_0100),
+            ("80", 0b0000_1000),
+            ("90", 0b0000_1100),
+        ],
+        default_value: "70",
+    },
+    DipSwitch {
+        name: "service_mode",
+        description: "Whether the machine starts in its test mode",
+        mask: 0b0001_0000,
+        values: &[("off", 0b0000_0000), ("on", 0b0001_0000)],
+        default_value: "off",
+    },
+];
+
+// From MAME
+pub const ROM_SET: RomSet = RomSet {
+    name: "boothill",
+    files: &[
+        RomFile {
+            file_name: "romh.cpu",
+            load_address: 0x0000,
+            size: 0x0800,
+            crc32: 0x1615_D077,
+            sha1: "e59a26c2f2fc67ab24301e22d2e3f33043acdf72",
+        },
+        RomFile {
+            file_name: "romg.cpu",
+            load_address: 0x0800,
+            size: 0x0800,
+            crc32: 0x65A9_0420,
+            sha1: "9f36c44b5ae5b912cdbbeb9ff11a42221b8362d2",
+        },
+        RomFile {
+            file_name: "romf.cpu",
+            load_address: 0x1000,
+            size: 0x0800,
+            crc32: 0x3FDA_FD79,
+            sha1: "b18e8ac9df40c4687ac1acd5174eb99f2ef60081",
+        },
+        RomFile {
+            file_name: "rome.cpu",
+            load_address: 0x1800,
+            size: 0x0800,
+            crc32: 0x3745_29F4,
+            sha1: "18c57b79df0c66052eef40a694779a5ade15d0e0",
+        },
+    ],
+};
+
+// The music tone generator counts up from the value written to ports 5 and 6 to 0xFFF, and
+// halves the frequency of its clock. Also from MAME's mw8080bw driver.
+const TONE_CLOCK_HZ: u32 = 19_968_000 / 10 / 2;
+const TONE_COUNTER_MAX: u32 = 0x1000;
+
+pub struct BootHillMachine {
+    state: State,
+    inputs: BootHillInputs,
+    dip_switches: DipSwitches,
+}
+
+impl BootHillMachine {
+    fn gun_state(&self, is_player_two: bool) -> u8 {
+        let (gun_up, gun_middle, gun_down) = if is_player_two {
+            (
+                self.inputs.p2_gun_up,
+                self.inputs.p2_gun_middle,
+                self.inputs.p2_gun_down,
+            )
+        } else {
+            (
+                self.inputs.p1_gun_up,
+                self.inputs.p1_gun_middle,
+                self.inputs.p1_gun_down,
+            )
+        };
+
+        // This maps the inputs to a so-called 'gun state', which is a set of bits
+        // the game uses to determine where the gun is pointing.
+        // This is not a complete set of these states (there are 7 distinct ones in total),
+        // but this is about as good as you can hope to get with a digital control scheme.
+        match (gun_up, gun_middle, gun_down) {
+            (true, true, false) => 0b001,  // 2nd top
+            (false, true, true) => 0b100,  // 2nd bottom
+            (true, false, false) => 0b101, // Top
+            (false, false, true) => 0b000, // Bottom
+            (false, true, false) => 0b110, // 3rd bottom
+            (_, _, _) => 0b111,            // Default
+        }
+    }
+}
+
+impl Default for BootHillMachine {
+    fn default() -> Self {
+        BootHillMachine {
+            state: State {
+                memory: Box::<MidwayMemory>::default(),
+                ports: Box::<BootHillPorts>::default(),
+                ..State::default()
+            },
+            inputs: BootHillInputs::default(),
+            dip_switches: DipSwitches::new(&DIP_SWITCHES),
+        }
+    }
+}
+
+impl Machine for BootHillMachine {
+    fn state(&self) -> &State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+
+    fn input_actions(&self) -> &'static [Input] {
+        &[
+            Input::Coin,
+            Input::P1Start,
+            Input::P2Start,
+            Input::P1Up,
+            Input::P1Left,
+            Input::P1Down,
+            Input::P1Right,
+            Input::P1AimUp,
+            Input::P1AimMiddle,
+            Input::P1AimDown,
+            Input::P1Fire,
+            Input::P2Up,
+            Input::P2Left,
+            Input::P2Down,
+            Input::P2Right,
+            Input::P2AimUp,
+            Input::P2AimMiddle,
+            Input::P2AimDown,
+            Input::P2Fire,
+        ]
+    }
+
+    fn set_input(&mut self, input: Input, is_pressed: bool) {
+        match input {
+            Input::Coin => self.inputs.credit = is_pressed,
+            Input::P1Start => self.inputs.p1_start = is_pressed,
+            Input::P2Start => self.inputs.p2_start = is_pressed,
+            Input::P1Up => self.inputs.p1_move_up = is_pressed,
+            Input::P1Down => self.inputs.p1_move_down = is_pressed,
+            Input::P1Left => self.inputs.p1_move_left = is_pressed,
+            Input::P1Right => self.inputs.p1_move_right = is_pressed,
+            Input::P1AimUp => self.inputs.p1_gun_up = is_pressed,
+            Input::P1AimMiddle => self.inputs.p1_gun_middle = is_pressed,
+            Input::P1AimDown => self.inputs.p1_gun_down = is_pressed,
+            Input::P1Fire => self.inputs.p1_shoot = is_pressed,
+            Input::P2Up => self.inputs.p2_up = is_pressed,
+            Input::P2Down => self.inputs.p2_down = is_pressed,
+            Input::P2Left => self.inputs.p2_left = is_pressed,
+            Input::P2Right => self.inputs.p2_right = is_pressed,
+            Input::P2AimUp => self.inputs.p2_gun_up = is_pressed,
+            Input::P2AimMiddle => self.inputs.p2_gun_middle = is_pressed,
+            Input::P2AimDown => self.inputs.p2_gun_down = is_pressed,
+            Input::P2Fire => self.inputs.p2_shoot = is_pressed,
+            _ => {}
+        };
+    }
+
+    fn set_ports_from_inputs(&mut self) {
+        let p2_gun_state = self.gun_state(true);
+
+        set_in_port_from_flags(
+            &mut self.state.ports,
+            0,
+            hashmap! {
+                0 => !self.inputs.p2_up,
+                1 => !self.inputs.p2_down,
+                2 => !self.inputs.p2_left,
+                3 => !self.inputs.p2_right,
+                4 => bit_operations::is_bit_set(p2_gun_state, 0),
+                5 => bit_operations::is_bit_set(p2_gun_state, 1),
+                6 => bit_operations::is_bit_set(p2_gun_state, 2),
+                7 => !self.inputs.p2_shoot,
+            },
+        );
+
+        let p1_gun_state = self.gun_state(false);
+
+        set_in_port_from_flags(
+            &mut self.state.ports,
+            1,
+            hashmap! {
+                0 => !self.inputs.p1_move_up,
+                1 => !self.inputs.p1_move_down,
+                2 => !self.inputs.p1_move_left,
+                3 => !self.inputs.p1_move_right,
+                4 => bit_operations::is_bit_set(p1_gun_state, 0),
+                5 => bit_operations::is_bit_set(p1_gun_state, 1),
+                6 => bit_operations::is_bit_set(p1_gun_state, 2),
+                7 => !self.inputs.p1_shoot,
+            },
+        );
+
+        set_in_port_from_flags(
+            &mut self.state.ports,
+            2,
+            hashmap! {
+                5 => !self.inputs.p1_start,
+                6 => !self.inputs.credit,
+                7 => !self.inputs.p2_start,
+                0 => self.dip_switches.is_bit_set(0),
+                1 => self.dip_switches.is_bit_set(1),
+                2 => self.dip_switches.is_bit_set(2),
+                3 => self.dip_switches.is_bit_set(3),
+                4 => self.dip_switches.is_bit_set(4),
+            },
+        );
+    }
+
+    fn dip_switches(&self) -> Option<&DipSwitches> {
+        Some(&self.dip_switches)
+    }
+
+    fn dip_switches_mut(&mut self) -> Option<&mut DipSwitches> {
+        Some(&mut self.dip_switches)
+    }
+
+    fn name(&self) -> &str {
+        "Boot Hill"
+    }
+}
+
+struct BootHillPorts {
+    shift_data: u16,
+    shift_amount: u8,
+    shift_reverse: bool,
+    in_port_0: u8,
+    in_port_1: u8,
+    in_port_2: u8,
+    out_port_3: u8,
+    out_port_5: u8,
+    out_port_6: u8,
+    watchdog: u8,
+    audio_events: Vec<AudioEvent>,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for BootHillPorts {
+    fn default() -> Self {
+        BootHillPorts {
+            shift_data: 0b0000_0000_0000_0000,
+            shift_amount: 0b0000_0000,
+            shift_reverse: false,
+            in_port_0: 0b0000_0000,
+            in_port_1: 0b0000_0000,
+            in_port_2: 0b0000_0000,
+            out_port_3: 0b0000_0000,
+            out_port_5: 0b0000_0000,
+            out_port_6: 0b0000_0000,
+            watchdog: 0b0000_0000,
+            audio_events: Vec::new(),
+        }
+    }
+}
+
+impl Ports for BootHillPorts {
+    fn read_in_port(&self, port_number: u8) -> Result<u8, CpuError> {
+        match port_number {
+            #[allow(clippy::manual_range_patterns)]
+            0 | 1 | 2 => Ok(self.in_port_static_value(port_number).unwrap()),
+            3 => {
+                let shifted_value = shift_value(self.shift_data, self.shift_amount);
+                if self.shift_reverse {
+                    Ok(bit_operations::reverse_byte(shifted_value))
+                } else {
+                    Ok(shifted_value)
+                }
+            }
+            _ => Err(CpuError::InvalidInputPort(port_number)),
+        }
+    }
+
+    fn write_out_port(&mut self, port_number: u8, value: u8) -> Result<(), CpuError> {
+        match port_number {
+            3 => {
+                // Bit 2 drives the coin counter, and bit 3 mutes all sound outside of a game
+                if bit_operations::is_bit_set(value, 3) {
+                    for (bit_index, sound_name) in [
+                        (4, SoundName::LeftShot),
+                        (5, SoundName::RightShot),
+                        (6, SoundName::LeftHit),
+                        (7, SoundName::RightHit),
+                    ] {
+                        if bit_operations::is_bit_set(value, bit_index)
+                            && !bit_operations::is_bit_set(self.out_port_3, bit_index)
+                        {
+                            self.audio_events.push(AudioEvent::Play(sound_name));
+                        }
+                    }
+                }
+                self.set_tone_ports(3, value);
+            }
+            5 | 6 => self.set_tone_ports(port_number, value),
+            1 => {
+                self.shift_amount = value & 0b0000_0111;
+                self.shift_reverse = value & 0b0000_1000 == 0b0000_1000;
+            }
+            2 => self.shift_data = shift_new_value_into_data(self.shift_data, value),
+            4 => {
+                self.watchdog = value;
+                debug!("Watchdog: {}", self.watchdog);
+            }
+            _ => return Err(CpuError::InvalidOutputPort(port_number)),
+        }
+
+        Ok(())
+    }
+
+    fn in_port_static_value(&self, port_number: u8) -> Option<u8> {
+        match port_number {
+            0 => Some(self.in_port_0),
+            1 => Some(self.in_port_1),
+            2 => Some(self.in_port_2),
+            _ => None,
+        }
+    }
+
+    fn set_in_port_static_value(&mut self, port_number: u8, value: u8) {
+        match port_number {
+            0 => self.in_port_0 = value,
+            1 => self.in_port_1 = value,
+            2 => self.in_port_2 = value,
+            _ => {}
+        }
+    }
+
+    fn save_state(&self, writer: &mut SaveStateWriter) {
+        writer.write_u16(self.shift_data);
+        writer.write_u8(self.shift_amount);
+        writer.write_bool(self.shift_reverse);
+        writer.write_u8(self.in_port_0);
+        writer.write_u8(self.in_port_1);
+        writer.write_u8(self.in_port_2);
+        writer.write_u8(self.out_port_3);
+        writer.write_u8(self.out_port_5);
+        writer.write_u8(self.out_port_6);
+        writer.write_u8(self.watchdog);
+    }
+
+    fn load_state(&mut self, reader: &mut SaveStateReader) -> Result<(), SaveStateError> {
+        let previous_frequency = self.tone_frequency();
+        self.shift_data = reader.read_u16()?;
+        self.shift_amount = reader.read_u8()?;
+        self.shift_reverse = reader.read_bool()?;
+        self.in_port_0 = reader.read_u8()?;
+        self.in_port_1 = reader.read_u8()?;
+        self.in_port_2 = reader.read_u8()?;
+        self.out_port_3 = reader.read_u8()?;
+        self.out_port_5 = reader.read_u8()?;
+        self.out_port_6 = reader.read_u8()?;
+        self.watchdog = reader.read_u8()?;
+
+        // The tone plays until it is changed, so it is set to the loaded one
+        let frequency = self.tone_frequency();
+        if frequency != previous_frequency {
+            self.audio_events.push(AudioEvent::SetTone(frequency));
+        }
+        Ok(())
+    }
+
+    fn take_audio_events(&mut self) -> Vec<AudioEvent> {
+        std::mem::take(&mut self.audio_events)
+    }
+}
+
+impl BootHillPorts {
+    // Port 5 holds the enable bit and the low 5 bits of the count, and port 6 the high 6 bits
+    fn tone_frequency(&self) -> Option<u32> {
+        if !bit_operations::is_bit_set(self.out_port_3, 3)
+            || !bit_operations::is_bit_set(self.out_port_5, 0)
+        {
+            return None;
+        }
+
+        let count_start = (u32::from(self.out_port_6 & 0b0011_1111) << 6)
+            | u32::from((self.out_port_5 >> 1) & 0b0001_1111);
+        Some(TONE_CLOCK_HZ / (TONE_COUNTER_MAX - count_start) / 2)
+    }
+
+    fn set_tone_ports(&mut self, port_number: u8, value: u8) {
+        let old_frequency = self.tone_frequency();
+        match port_number {
+            3 => self.out_port_3 = value,
+            5 => self.out_port_5 = value,
+            _ => self.out_port_6 = value,
+        }
+
+        let new_frequency = self.tone_frequency();
+        if new_frequency != old_frequency {
+            self.audio_events.push(AudioEvent::SetTone(new_frequency));
+        }
+    }
+}
+
+#[derive(Default)]
+struct BootHillInputs {
+    credit: bool,
+    p1_start: bool,
+    p1_move_up: bool,
+    p1_move_down: bool,
+    p1_move_left: bool,
+    p1_move_right: bool,
+    p1_gun_up: bool,
+    p1_gun_middle: bool,
+    p1_gun_down: bool,
+    p1_shoot: bool,
+    p2_start: bool,
+    p2_up: bool,
+    p2_down: bool,
+    p2_left: bool,
+    p2_right: bool,
+    p2_gun_up: bool,
+    p2_gun_middle: bool,
+    p2_gun_down: bool,
+    p2_shoot: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_register_can_be_read_reversed() {
+        let mut ports = BootHillPorts::default();
+        ports.write_out_port(2, 0xAB).unwrap();
+        ports.write_out_port(2, 0xCD).unwrap();
+        ports.write_out_port(1, 0x04).unwrap();
+        assert_eq!(ports.read_in_port(3).unwrap(), 0xDA);
+
+        ports.write_out_port(1, 0x0C).unwrap();
+        assert_eq!(ports.read_in_port(3).unwrap(), 0x5B);
+    }
+
+    #[test]
+    fn aim_inputs_are_mapped_to_gun_states() {
+        let mut machine = BootHillMachine::default();
+        assert_eq!(machine.gun_state(false), 0b111);
+
+        machine.set_input(Input::P1AimUp, true);
+        assert_eq!(machine.gun_state(false), 0b101);
+
+        machine.set_input(Input::P1AimMiddle, true);
+        assert_eq!(machine.gun_state(false), 0b001);
+        assert_eq!(machine.gun_state(true), 0b111);
+
+        machine.set_ports_from_inputs();
+        assert_eq!(machine.state().ports.read_in_port(1).unwrap(), 0b1001_1111);
+    }
+
+    #[test]
+    fn shots_and_hits_are_played_when_their_bit_is_first_set_during_a_game() {
+        let mut ports = BootHillPorts::default();
+        ports.write_out_port(3, 0b0001_0000).unwrap();
+        assert!(ports.take_audio_events().is_empty());
+
+        ports.write_out_port(3, 0b0000_1000).unwrap();
+        ports.write_out_port(3, 0b0101_1000).unwrap();
+        ports.write_out_port(3, 0b0101_1000).unwrap();
+        ports.write_out_port(3, 0b1000_1000).unwrap();
+
+        assert_eq!(
+            ports.take_audio_events(),
+            vec![
+                AudioEvent::Play(SoundName::LeftShot),
+                AudioEvent::Play(SoundName::LeftHit),
+                AudioEvent::Play(SoundName::RightHit)
+            ]
+        );
+    }
+
+    #[test]
+    fn music_tone_changes_with_the_counter_value_while_enabled() {
+        let mut ports = BootHillPorts::default();
+        ports.write_out_port(3, 0b0000_1000).unwrap();
+        ports.write_out_port(6, 0b0011_1000).unwrap();
+        assert!(ports.take_audio_events().is_empty());
+
+        ports.write_out_port(5, 0b0000_0001).unwrap();
+        ports.write_out_port(5, 0b0000_0001).unwrap();
+        ports.write_out_port(5, 0b0010_0001).unwrap();
+        ports.write_out_port(3, 0b0000_0000).unwrap();
+
+        assert_eq!(
+            ports.take_audio_events(),
+            vec![
+                AudioEvent::SetTone(Some(975)),
+                AudioEvent::SetTone(Some(1_006)),
+                AudioEvent::SetTone(None)
+            ]
+        );
+    }
+
+    #[test]
+    fn loading_a_state_sets_the_tone_to_the_loaded_one() {
+        let mut ports = BootHillPorts::default();
+        ports.write_out_port(3, 0b0000_1000).unwrap();
+        ports.write_out_port(6, 0b0011_1000).unwrap();
+        ports.write_out_port(5, 0b0000_0001).unwrap();
+        let mut writer = SaveStateWriter::default();
+        ports.save_state(&mut writer);
+        let bytes_with_tone = writer.into_bytes();
+
+        ports.write_out_port(5, 0b0010_0001).unwrap();
+        ports.take_audio_events();
+        ports
+            .load_state(&mut SaveStateReader::new(&bytes_with_tone))
+            .unwrap();
+        assert_eq!(
+            ports.take_audio_events(),
+            vec![AudioEvent::SetTone(Some(975))]
+        );
+
+        let mut writer = SaveStateWriter::default();
+        BootHillPorts::default().save_state(&mut writer);
+        ports
+            .load_state(&mut SaveStateReader::new(&writer.into_bytes()))
+            .unwrap();
+        assert_eq!(ports.take_audio_events(), vec![AudioEvent::SetTone(None)]);
+    }
+}
@@ -1,14 +1,21 @@
-use crate::{Condition, ConditionFlag, RegisterPair, State, bit_operations};
+use crate::{Condition, ConditionFlag, CpuError, RegisterPair, State, bit_operations};
 // #[cfg(test)]
 // use mutagen::mutate;
 
 // #[cfg_attr(test, mutate)]
-fn is_condition_true(state: &State, condition: Condition, base_instruction: &str) -> bool {
+fn is_condition_true(
+    state: &State,
+    condition: Condition,
+    base_instruction: &'static str,
+) -> Result<bool, CpuError> {
     if condition.0 == ConditionFlag::AuxiliaryCarry {
-        panic!("The auxiliary carry flag is not a supported condition for {base_instruction}");
+        return Err(CpuError::UnsupportedCondition(
+            condition.0,
+            base_instruction,
+        ));
     }
 
-    state.is_condition_true(condition)
+    Ok(state.is_condition_true(condition))
 }
 
 // #[cfg_attr(test, mutate)]
@@ -17,10 +24,16 @@ pub fn jmp_instruction(state: &mut State, low_data: u8, high_data: u8) {
 }
 
 // #[cfg_attr(test, mutate)]
-pub fn jcond_instruction(state: &mut State, low_data: u8, high_data: u8, condition: Condition) {
-    if is_condition_true(state, condition, "JMP") {
+pub fn jcond_instruction(
+    state: &mut State,
+    low_data: u8,
+    high_data: u8,
+    condition: Condition,
+) -> Result<(), CpuError> {
+    if is_condition_true(state, condition, "JMP")? {
         jmp_instruction(state, low_data, high_data);
     }
+    Ok(())
 }
 
 // #[cfg_attr(test, mutate)]
@@ -28,44 +41,52 @@ pub fn call_instruction(state: &mut State, low_data: u8, high_data: u8) {
     let (pc_low, pc_high) = bit_operations::split_to_low_high_bytes(state.program_counter);
     let sp_minus_one = state.stack_pointer.wrapping_sub(1);
     let sp_minus_two = state.stack_pointer.wrapping_sub(2);
-    state.memory[sp_minus_one as usize] = pc_high;
-    state.memory[sp_minus_two as usize] = pc_low;
+    state.write_memory(sp_minus_one, pc_high);
+    state.write_memory(sp_minus_two, pc_low);
     state.stack_pointer = sp_minus_two;
     state.program_counter = bit_operations::concat_low_high_bytes(low_data, high_data);
 }
 
 // #[cfg_attr(test, mutate)]
-pub fn ccond_instruction(state: &mut State, low_data: u8, high_data: u8, condition: Condition) {
-    if is_condition_true(state, condition, "CALL") {
+pub fn ccond_instruction(
+    state: &mut State,
+    low_data: u8,
+    high_data: u8,
+    condition: Condition,
+) -> Result<(), CpuError> {
+    if is_condition_true(state, condition, "CALL")? {
         call_instruction(state, low_data, high_data);
     }
+    Ok(())
 }
 
 // #[cfg_attr(test, mutate)]
 pub fn ret_instruction(state: &mut State) {
     let sp_plus_one = state.stack_pointer.wrapping_add(1);
     let sp_plus_two = state.stack_pointer.wrapping_add(2);
-    let value_for_pc_low = state.memory[state.stack_pointer as usize];
-    let value_for_pc_high = state.memory[sp_plus_one as usize];
+    let value_for_pc_low = state.read_memory(state.stack_pointer);
+    let value_for_pc_high = state.read_memory(sp_plus_one);
     state.program_counter =
         bit_operations::concat_low_high_bytes(value_for_pc_low, value_for_pc_high);
     state.stack_pointer = sp_plus_two;
 }
 
 // #[cfg_attr(test, mutate)]
-pub fn rcond_instruction(state: &mut State, condition: Condition) {
-    if is_condition_true(state, condition, "RET") {
+pub fn rcond_instruction(state: &mut State, condition: Condition) -> Result<(), CpuError> {
+    if is_condition_true(state, condition, "RET")? {
         ret_instruction(state);
     }
+    Ok(())
 }
 
 // #[cfg_attr(test, mutate)]
-pub fn rst_instruction(state: &mut State, reset_index: u8) {
+pub fn rst_instruction(state: &mut State, reset_index: u8) -> Result<(), CpuError> {
     if reset_index >= 8 {
-        panic!("Invalid reset index of {reset_index}");
+        return Err(CpuError::InvalidResetIndex(reset_index));
     }
 
     call_instruction(state, reset_index * 8, 0x00);
+    Ok(())
 }
 
 // #[cfg_attr(test, mutate)]
@@ -93,7 +114,7 @@ mod tests {
     #[test]
     fn jcond_sets_the_program_counter_when_condition_is_true() {
         let mut state = State::default();
-        jcond_instruction(&mut state, 0xFF, 0xFF, (ConditionFlag::Zero, false));
+        jcond_instruction(&mut state, 0xFF, 0xFF, (ConditionFlag::Zero, false)).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default().program_counter(0xFFFF).build(),
@@ -103,7 +124,7 @@ mod tests {
     #[test]
     fn jcond_does_not_set_the_program_counter_when_condition_is_false() {
         let mut state = State::default();
-        jcond_instruction(&mut state, 0xFF, 0xFF, (ConditionFlag::Zero, true));
+        jcond_instruction(&mut state, 0xFF, 0xFF, (ConditionFlag::Zero, true)).unwrap();
         assert_state_is_as_expected(&state, &State::default());
     }
 
@@ -112,7 +133,7 @@ mod tests {
         let mut state = StateBuilder::default()
             .condition_flag_values(hashmap! { ConditionFlag::Carry => true })
             .build();
-        jcond_instruction(&mut state, 0x0F, 0x00, (ConditionFlag::Carry, true));
+        jcond_instruction(&mut state, 0x0F, 0x00, (ConditionFlag::Carry, true)).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -123,16 +144,21 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "The auxiliary carry flag is not a supported condition for JMP")]
     fn jcond_does_not_support_auxiliary_carry_as_condition() {
         let mut state = StateBuilder::default()
             .condition_flag_values(hashmap! { ConditionFlag::AuxiliaryCarry => true })
             .build();
-        jcond_instruction(
-            &mut state,
-            0x0D,
-            0xF0,
-            (ConditionFlag::AuxiliaryCarry, true),
+        assert_eq!(
+            jcond_instruction(
+                &mut state,
+                0x0D,
+                0xF0,
+                (ConditionFlag::AuxiliaryCarry, true),
+            ),
+            Err(CpuError::UnsupportedCondition(
+                ConditionFlag::AuxiliaryCarry,
+                "JMP"
+            ))
         );
     }
 
@@ -193,7 +219,7 @@ mod tests {
             .stack_pointer(0x77E1)
             .condition_flag_values(hashmap! { ConditionFlag::Zero => true })
             .build();
-        ccond_instruction(&mut state, 0x6F, 0x7B, (ConditionFlag::Zero, true));
+        ccond_instruction(&mut state, 0x6F, 0x7B, (ConditionFlag::Zero, true)).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -212,7 +238,7 @@ mod tests {
             .stack_pointer(0x77E1)
             .condition_flag_values(hashmap! { ConditionFlag::Zero => true })
             .build();
-        ccond_instruction(&mut state, 0x6F, 0x7B, (ConditionFlag::Zero, false));
+        ccond_instruction(&mut state, 0x6F, 0x7B, (ConditionFlag::Zero, false)).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -224,16 +250,21 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "The auxiliary carry flag is not a supported condition for CALL")]
     fn ccond_does_not_support_auxiliary_carry_as_condition() {
         let mut state = StateBuilder::default()
             .condition_flag_values(hashmap! { ConditionFlag::AuxiliaryCarry => true })
             .build();
-        ccond_instruction(
-            &mut state,
-            0x0D,
-            0xF0,
-            (ConditionFlag::AuxiliaryCarry, false),
+        assert_eq!(
+            ccond_instruction(
+                &mut state,
+                0x0D,
+                0xF0,
+                (ConditionFlag::AuxiliaryCarry, false),
+            ),
+            Err(CpuError::UnsupportedCondition(
+                ConditionFlag::AuxiliaryCarry,
+                "CALL"
+            ))
         );
     }
 
@@ -280,7 +311,7 @@ mod tests {
             .memory_values(hashmap! { 0xA462 => 72, 0xA463 => 201 })
             .condition_flag_values(hashmap! { ConditionFlag::Parity => true })
             .build();
-        rcond_instruction(&mut state, (ConditionFlag::Parity, true));
+        rcond_instruction(&mut state, (ConditionFlag::Parity, true)).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -299,7 +330,7 @@ mod tests {
             .memory_values(hashmap! { 0xA462 => 72, 0xA463 => 201 })
             .condition_flag_values(hashmap! { ConditionFlag::Parity => true })
             .build();
-        rcond_instruction(&mut state, (ConditionFlag::Parity, false));
+        rcond_instruction(&mut state, (ConditionFlag::Parity, false)).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -311,10 +342,15 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "The auxiliary carry flag is not a supported condition for RET")]
     fn rcond_does_not_support_auxiliary_carry_as_condition() {
         let mut state = State::default();
-        rcond_instruction(&mut state, (ConditionFlag::AuxiliaryCarry, true));
+        assert_eq!(
+            rcond_instruction(&mut state, (ConditionFlag::AuxiliaryCarry, true)),
+            Err(CpuError::UnsupportedCondition(
+                ConditionFlag::AuxiliaryCarry,
+                "RET"
+            ))
+        );
     }
 
     #[test]
@@ -323,7 +359,7 @@ mod tests {
             .program_counter(0x1914)
             .stack_pointer(0x9DEC)
             .build();
-        rst_instruction(&mut state, 7);
+        rst_instruction(&mut state, 7).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -335,10 +371,12 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Invalid reset index of 8")]
-    fn rst_panics_when_given_an_invalid_reset_index() {
+    fn rst_rejects_an_invalid_reset_index() {
         let mut state = State::default();
-        rst_instruction(&mut state, 8);
+        assert_eq!(
+            rst_instruction(&mut state, 8),
+            Err(CpuError::InvalidResetIndex(8))
+        );
     }
 
     #[test]
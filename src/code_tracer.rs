@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::Operation;
+use crate::disassembler::{self, Instruction};
+
+// The reset vector, and the RST 1 and RST 2 vectors used by the video interrupts
+pub const DEFAULT_ENTRY_POINTS: [u16; 3] = [0x0000, 0x0008, 0x0010];
+
+const MAX_DATA_BYTES_PER_LINE: usize = 8;
+
+pub struct CodeMap {
+    origin: u16,
+    is_code: Vec<bool>,
+    instructions: BTreeMap<u16, Instruction>,
+    labels: BTreeSet<u16>,
+    indirect_jumps: BTreeSet<u16>,
+}
+
+impl CodeMap {
+    pub fn is_code(&self, address: u16) -> bool {
+        self.index_of(address)
+            .is_some_and(|index| self.is_code[index])
+    }
+
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.instructions.values()
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = u16> + '_ {
+        self.labels.iter().copied()
+    }
+
+    pub fn indirect_jumps(&self) -> impl Iterator<Item = u16> + '_ {
+        self.indirect_jumps.iter().copied()
+    }
+
+    pub fn label_name(&self, address: u16) -> Option<String> {
+        self.labels
+            .contains(&address)
+            .then(|| format!("L{address:04X}"))
+    }
+
+    pub fn listing(&self, bytes: &[u8]) -> String {
+        let mut listing = String::new();
+        let mut index = 0;
+
+        while index < bytes.len() && index < self.is_code.len() {
+            let address = self.origin.wrapping_add(index as u16);
+
+            if let Some(label_name) = self.label_name(address) {
+                listing += &format!("{label_name}:\n");
+            }
+
+            if let Some(instruction) = self.instructions.get(&address) {
+                listing += &disassembler::format_listing_line(
+                    address,
+                    &instruction.bytes,
+                    &self.instruction_text(instruction),
+                );
+                index += instruction.bytes.len();
+            } else {
+                let data_length = self.data_length_from(index);
+                let data_bytes = &bytes[index..index + data_length];
+                listing += &disassembler::format_listing_line(
+                    address,
+                    data_bytes,
+                    &disassembler::format_data_bytes(data_bytes),
+                );
+                index += data_length;
+            }
+
+            listing.push('\n');
+        }
+
+        listing
+    }
+
+    fn index_of(&self, address: u16) -> Option<usize> {
+        let index = usize::from(address.checked_sub(self.origin)?);
+        (index < self.is_code.len()).then_some(index)
+    }
+
+    fn data_length_from(&self, start_index: usize) -> usize {
+        let mut data_length = 0;
+
+        while data_length < MAX_DATA_BYTES_PER_LINE
+            && start_index + data_length < self.is_code.len()
+            && !self.is_code[start_index + data_length]
+        {
+            data_length += 1;
+
+            let next_address = self.origin.wrapping_add((start_index + data_length) as u16);
+            if self.labels.contains(&next_address) {
+                break;
+            }
+        }
+
+        data_length
+    }
+
+    fn instruction_text(&self, instruction: &Instruction) -> String {
+        match instruction.operation {
+            Operation::Jmp | Operation::Jcond(_) | Operation::Call | Operation::Ccond(_) => {
+                let target_address = instruction.data().unwrap();
+                match self.label_name(target_address) {
+                    Some(label_name) => format!(
+                        "{} {label_name}",
+                        disassembler::mnemonic(&instruction.operation)
+                    ),
+                    None => instruction.to_string(),
+                }
+            }
+            Operation::Pchl => format!("{instruction}  ; unresolved indirect jump"),
+            _ => instruction.to_string(),
+        }
+    }
+}
+
+// Follows every branch reachable from the entry points to work out which bytes are code;
+// anything that is never reached is treated as data.
+pub fn trace(bytes: &[u8], origin: u16, entry_points: &[u16]) -> CodeMap {
+    let mut code_map = CodeMap {
+        origin,
+        is_code: vec![false; bytes.len().min(usize::from(u16::MAX) + 1)],
+        instructions: BTreeMap::new(),
+        labels: BTreeSet::new(),
+        indirect_jumps: BTreeSet::new(),
+    };
+    let mut addresses_to_trace: Vec<u16> = entry_points.iter().rev().copied().collect();
+
+    for entry_point in entry_points {
+        if code_map.index_of(*entry_point).is_some() {
+            code_map.labels.insert(*entry_point);
+        }
+    }
+
+    while let Some(start_address) = addresses_to_trace.pop() {
+        let mut address = start_address;
+
+        while code_map
+            .index_of(address)
+            .is_some_and(|index| !code_map.is_code[index])
+        {
+            let Some(instruction) = disassembler::disassemble_instruction(bytes, origin, address)
+            else {
+                break;
+            };
+
+            for offset in 0..instruction.length() {
+                if let Some(index) = code_map.index_of(address.wrapping_add(offset)) {
+                    code_map.is_code[index] = true;
+                }
+            }
+
+            let mut add_target = |target_address: u16| {
+                if code_map.index_of(target_address).is_some() {
+                    code_map.labels.insert(target_address);
+                    addresses_to_trace.push(target_address);
+                }
+            };
+
+            let does_flow_continue = match instruction.operation {
+                Operation::Jmp => {
+                    add_target(instruction.data().unwrap());
+                    false
+                }
+                Operation::Jcond(_) | Operation::Call | Operation::Ccond(_) => {
+                    add_target(instruction.data().unwrap());
+                    true
+                }
+                Operation::Rst(reset_index) => {
+                    add_target(u16::from(reset_index) * 8);
+                    true
+                }
+                Operation::Pchl => {
+                    code_map.indirect_jumps.insert(address);
+                    false
+                }
+                Operation::Ret => false,
+                _ => true,
+            };
+
+            address = instruction.next_address();
+            code_map
+                .instructions
+                .insert(instruction.address, instruction);
+
+            if !does_flow_continue {
+                break;
+            }
+        }
+    }
+
+    code_map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_follows_jumps_and_skips_unreachable_bytes() {
+        let bytes = [
+            0xC3, 0x05, 0x00, // JMP 0005
+            0x12, 0x34, // Data
+            0x3E, 0x01, // MVI A,01
+            0xC9, // RET
+        ];
+        let code_map = trace(&bytes, 0x0000, &[0x0000]);
+
+        let code_bytes: Vec<bool> = (0..bytes.len() as u16)
+            .map(|address| code_map.is_code(address))
+            .collect();
+        assert_eq!(
+            code_bytes,
+            vec![true, true, true, false, false, true, true, true]
+        );
+        assert_eq!(code_map.labels().collect::<Vec<_>>(), vec![0x0000, 0x0005]);
+    }
+
+    #[test]
+    fn trace_follows_both_paths_of_conditional_branches_and_calls() {
+        let bytes = [
+            0xCA, 0x07, 0x00, // JZ 0007
+            0xCD, 0x08, 0x00, // CALL 0008
+            0x76, // HLT
+            0xC9, // RET
+            0xC9, // RET
+        ];
+        let code_map = trace(&bytes, 0x0000, &[0x0000]);
+
+        assert!((0..bytes.len() as u16).all(|address| code_map.is_code(address)));
+        assert_eq!(
+            code_map.labels().collect::<Vec<_>>(),
+            vec![0x0000, 0x0007, 0x0008]
+        );
+    }
+
+    #[test]
+    fn trace_follows_rst_targets() {
+        let mut bytes = vec![0xD7, 0xC9]; // RST 2, RET
+        bytes.resize(0x10, 0xFF);
+        bytes.push(0xC9); // RET at 0x0010
+        let code_map = trace(&bytes, 0x0000, &[0x0000]);
+
+        assert!(code_map.is_code(0x0010));
+        assert!(!code_map.is_code(0x0008));
+        assert_eq!(code_map.labels().collect::<Vec<_>>(), vec![0x0000, 0x0010]);
+    }
+
+    #[test]
+    fn trace_flags_pchl_as_an_unresolved_indirect_jump() {
+        let bytes = [0x21, 0x00, 0x10, 0xE9, 0x00];
+        let code_map = trace(&bytes, 0x0000, &[0x0000]);
+
+        assert_eq!(code_map.indirect_jumps().collect::<Vec<_>>(), vec![0x0003]);
+        assert!(!code_map.is_code(0x0004));
+    }
+
+    #[test]
+    fn trace_ignores_targets_outside_of_the_bytes() {
+        let bytes = [0xC3, 0x00, 0x20];
+        let code_map = trace(&bytes, 0x0000, &[0x0000]);
+
+        assert_eq!(code_map.labels().collect::<Vec<_>>(), vec![0x0000]);
+        assert_eq!(code_map.instructions().count(), 1);
+    }
+
+    #[test]
+    fn listing_shows_labels_and_data_bytes() {
+        let bytes = [
+            0xC3, 0x05, 0x00, // JMP 0005
+            0x48, 0x49, // Data
+            0xC2, 0x05, 0x00, // JNZ 0005
+            0xE9, // PCHL
+        ];
+        let code_map = trace(&bytes, 0x0100, &[0x0100, 0x0105]);
+
+        assert_eq!(
+            code_map.listing(&bytes),
+            "L0100:\n\
+             0100  C3 05 00  JMP 0005h\n\
+             0103  48 49     DB 48h,49h\n\
+             L0105:\n\
+             0105  C2 05 00  JNZ 0005h\n\
+             0108  E9        PCHL  ; unresolved indirect jump\n"
+        );
+
+        let code_map = trace(&bytes, 0x0000, &[0x0000]);
+
+        assert_eq!(
+            code_map.listing(&bytes),
+            "L0000:\n\
+             0000  C3 05 00  JMP L0005\n\
+             0003  48 49     DB 48h,49h\n\
+             L0005:\n\
+             0005  C2 05 00  JNZ L0005\n\
+             0008  E9        PCHL  ; unresolved indirect jump\n"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use log::{info, warn};
+use sdl2::GameControllerSubsystem;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+
+use emu_8080::machine::Input;
+
+const NUM_PLAYERS: usize = 2;
+// Stick positions closer to the centre than this are ignored
+const AXIS_DEAD_ZONE: i16 = 8_000;
+
+struct PlayerInputs {
+    start: Input,
+    up: Input,
+    down: Input,
+    left: Input,
+    right: Input,
+    aim_up: Input,
+    aim_middle: Input,
+    aim_down: Input,
+    fire: Input,
+}
+
+const PLAYER_INPUTS: [PlayerInputs; NUM_PLAYERS] = [
+    PlayerInputs {
+        start: Input::P1Start,
+        up: Input::P1Up,
+        down: Input::P1Down,
+        left: Input::P1Left,
+        right: Input::P1Right,
+        aim_up: Input::P1AimUp,
+        aim_middle: Input::P1AimMiddle,
+        aim_down: Input::P1AimDown,
+        fire: Input::P1Fire,
+    },
+    PlayerInputs {
+        start: Input::P2Start,
+        up: Input::P2Up,
+        down: Input::P2Down,
+        left: Input::P2Left,
+        right: Input::P2Right,
+        aim_up: Input::P2AimUp,
+        aim_middle: Input::P2AimMiddle,
+        aim_down: Input::P2AimDown,
+        fire: Input::P2Fire,
+    },
+];
+
+// The first controller plugged in plays as player 1, and the second as player 2. A controller
+// plugged in after one is removed takes over the free player.
+pub struct Controllers {
+    subsystem: GameControllerSubsystem,
+    players: [Option<GameController>; NUM_PLAYERS],
+    // -1, 0 or 1 for each stick axis, so only changes of direction are sent as inputs
+    axis_directions: HashMap<(u32, Axis), i8>,
+}
+
+impl Controllers {
+    pub fn new(subsystem: GameControllerSubsystem) -> Self {
+        Controllers {
+            subsystem,
+            players: [None, None],
+            axis_directions: HashMap::new(),
+        }
+    }
+
+    // Returns the inputs to press or release for a controller event
+    pub fn handle_event(&mut self, event: &Event) -> Vec<(Input, bool)> {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                self.add_controller(which);
+                Vec::new()
+            }
+            Event::ControllerDeviceRemoved { which, .. } => self.remove_controller(which),
+            Event::ControllerButtonDown { which, button, .. } => self
+                .button_input(which, button)
+                .map(|input| vec![(input, true)])
+                .unwrap_or_default(),
+            Event::ControllerButtonUp { which, button, .. } => self
+                .button_input(which, button)
+                .map(|input| vec![(input, false)])
+                .unwrap_or_default(),
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => self.axis_inputs(which, axis, value),
+            _ => Vec::new(),
+        }
+    }
+
+    fn add_controller(&mut self, joystick_index: u32) {
+        let Some(player_index) = self.players.iter().position(Option::is_none) else {
+            info!("Ignoring controller {joystick_index}, as both players already have one");
+            return;
+        };
+
+        match self.subsystem.open(joystick_index) {
+            Ok(controller) => {
+                info!(
+                    "Controller \"{}\" is player {}",
+                    controller.name(),
+                    player_index + 1
+                );
+                self.players[player_index] = Some(controller);
+            }
+            Err(e) => warn!("Could not open controller {joystick_index}: {e}"),
+        }
+    }
+
+    // Releases everything the controller could have been holding, so no input gets stuck on
+    fn remove_controller(&mut self, instance_id: u32) -> Vec<(Input, bool)> {
+        let Some(player_index) = self.player_index(instance_id) else {
+            return Vec::new();
+        };
+
+        if let Some(controller) = self.players[player_index].take() {
+            info!(
+                "Controller \"{}\" for player {} was removed",
+                controller.name(),
+                player_index + 1
+            );
+        }
+        self.axis_directions.retain(|(id, _), _| *id != instance_id);
+
+        let player_inputs = &PLAYER_INPUTS[player_index];
+        [
+            Input::Coin,
+            player_inputs.start,
+            player_inputs.up,
+            player_inputs.down,
+            player_inputs.left,
+            player_inputs.right,
+            player_inputs.aim_up,
+            player_inputs.aim_middle,
+            player_inputs.aim_down,
+            player_inputs.fire,
+        ]
+        .into_iter()
+        .map(|input| (input, false))
+        .collect()
+    }
+
+    fn player_index(&self, instance_id: u32) -> Option<usize> {
+        self.players.iter().position(|controller| {
+            controller
+                .as_ref()
+                .is_some_and(|controller| controller.instance_id() == instance_id)
+        })
+    }
+
+    fn button_input(&self, instance_id: u32, button: Button) -> Option<Input> {
+        let player_inputs = &PLAYER_INPUTS[self.player_index(instance_id)?];
+
+        match button {
+            Button::Back => Some(Input::Coin),
+            Button::Start => Some(player_inputs.start),
+            Button::DPadUp => Some(player_inputs.up),
+            Button::DPadDown => Some(player_inputs.down),
+            Button::DPadLeft => Some(player_inputs.left),
+            Button::DPadRight => Some(player_inputs.right),
+            Button::A | Button::RightShoulder => Some(player_inputs.fire),
+            Button::Y => Some(player_inputs.aim_up),
+            Button::X | Button::LeftShoulder => Some(player_inputs.aim_middle),
+            Button::B => Some(player_inputs.aim_down),
+            _ => None,
+        }
+    }
+
+    // The left stick moves, and the right stick aims up and down
+    fn axis_inputs(&mut self, instance_id: u32, axis: Axis, value: i16) -> Vec<(Input, bool)> {
+        let Some(player_index) = self.player_index(instance_id) else {
+            return Vec::new();
+        };
+        let player_inputs = &PLAYER_INPUTS[player_index];
+
+        let (negative_input, positive_input) = match axis {
+            Axis::LeftX => (player_inputs.left, player_inputs.right),
+            Axis::LeftY => (player_inputs.up, player_inputs.down),
+            Axis::RightY => (player_inputs.aim_up, player_inputs.aim_down),
+            _ => return Vec::new(),
+        };
+
+        let direction = if value < -AXIS_DEAD_ZONE {
+            -1
+        } else if value > AXIS_DEAD_ZONE {
+            1
+        } else {
+            0
+        };
+        let previous_direction = self
+            .axis_directions
+            .insert((instance_id, axis), direction)
+            .unwrap_or(0);
+
+        if direction == previous_direction {
+            Vec::new()
+        } else {
+            vec![
+                (negative_input, direction == -1),
+                (positive_input, direction == 1),
+            ]
+        }
+    }
+}
@@ -0,0 +1,751 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+use crate::disassembler::{self, Instruction};
+use crate::watchpoints::{BreakEvent, PortDirection, WatchKind};
+use crate::{
+    ConditionFlag, CpuFault, CpuStop, Operation, Register, RegisterPair, State, runner, scheduler,
+};
+
+const DEFAULT_MEMORY_DUMP_LENGTH: u16 = 64;
+const DEFAULT_LIST_COUNT: u16 = 10;
+const MEMORY_DUMP_ROW_LENGTH: u16 = 16;
+// So that running a program stuck in a loop comes back to the prompt
+const DEFAULT_FRAME_LIMIT: u16 = 0x100;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StopReason {
+    Stepped,
+    Breakpoint(u16),
+    Returned,
+    Halted,
+    Watchpoint(BreakEvent),
+    Fault(CpuFault),
+    FrameLimit,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::Stepped => write!(f, "Stepped"),
+            StopReason::Breakpoint(address) => write!(f, "Hit breakpoint at {address:04X}"),
+            StopReason::Returned => write!(f, "Returned from subroutine"),
+            StopReason::Halted => write!(f, "CPU is halted"),
+            StopReason::Watchpoint(event) => {
+                write!(f, "Hit {}: {event}", event.breakpoint_kind())
+            }
+            StopReason::Fault(fault) => write!(f, "CPU fault: {fault}"),
+            StopReason::FrameLimit => write!(f, "Stopped at the frame limit"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+}
+
+impl Debugger {
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    // Raises the video interrupts at the same instruction boundaries as scheduler::run_frame. A
+    // halted CPU waits for the next interrupt, unless interrupts are disabled.
+    pub fn step(&self, state: &mut State) -> StopReason {
+        let (interrupt_state_count, reset_index) =
+            scheduler::next_video_interrupt(state.cpu_total_state_count());
+
+        let reason = if state.is_halted {
+            if !state.are_interrupts_enabled {
+                return StopReason::Halted;
+            }
+            state.skip_to_state_count(interrupt_state_count);
+            StopReason::Stepped
+        } else {
+            match runner::try_run_next_operation(state) {
+                Ok(()) => StopReason::Stepped,
+                Err(CpuStop::Break(event)) => StopReason::Watchpoint(event),
+                Err(CpuStop::Fault(fault)) => return StopReason::Fault(fault),
+            }
+        };
+
+        if state.cpu_total_state_count() >= interrupt_state_count {
+            scheduler::raise_interrupt(state, reset_index)
+                .expect("Video interrupts should have valid reset indexes");
+        }
+        reason
+    }
+
+    pub fn continue_execution(&self, state: &mut State, frame_limit: u16) -> StopReason {
+        self.run_until(state, frame_limit, |_, _| false)
+    }
+
+    // Runs a whole subroutine as a single step when the next instruction is a call
+    pub fn step_over(&self, state: &mut State) -> StopReason {
+        let instruction = disassembler::disassemble_state_instruction(state, state.program_counter);
+
+        match instruction.operation {
+            Operation::Call | Operation::Ccond(_) | Operation::Rst(_) => {
+                let return_address = instruction.next_address();
+                let stack_pointer = state.stack_pointer;
+                self.run_until(state, DEFAULT_FRAME_LIMIT, |state, _| {
+                    state.program_counter == return_address && state.stack_pointer == stack_pointer
+                })
+            }
+            _ => self.step(state),
+        }
+    }
+
+    pub fn run_until_return(&self, state: &mut State) -> StopReason {
+        let stack_pointer = state.stack_pointer;
+        let reason = self.run_until(state, DEFAULT_FRAME_LIMIT, |state, instruction| {
+            matches!(instruction.operation, Operation::Ret | Operation::Rcond(_))
+                && state.stack_pointer > stack_pointer
+        });
+
+        if reason == StopReason::Stepped {
+            StopReason::Returned
+        } else {
+            reason
+        }
+    }
+
+    // Keeps stepping until the condition is met after an instruction, or execution stops for another reason.
+    // Breakpoints are only checked after the first instruction, so that execution can continue from one.
+    fn run_until<F>(&self, state: &mut State, frame_limit: u16, is_finished: F) -> StopReason
+    where
+        F: Fn(&State, &Instruction) -> bool,
+    {
+        let end_state_count = scheduler::frame_start_state_count(
+            scheduler::frame_number(state.cpu_total_state_count()) + usize::from(frame_limit),
+        );
+
+        loop {
+            let instruction =
+                disassembler::disassemble_state_instruction(state, state.program_counter);
+            let reason = self.step(state);
+
+            if reason != StopReason::Stepped || is_finished(state, &instruction) {
+                return reason;
+            }
+
+            if self.breakpoints.contains(&state.program_counter) {
+                return StopReason::Breakpoint(state.program_counter);
+            }
+
+            if state.cpu_total_state_count() >= end_state_count {
+                return StopReason::FrameLimit;
+            }
+        }
+    }
+
+    // Returns whether the debugger should quit
+    pub fn execute_command(
+        &mut self,
+        state: &mut State,
+        command_line: &str,
+        output: &mut dyn Write,
+    ) -> io::Result<bool> {
+        let mut words = command_line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(false);
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        let numbers = match arguments
+            .iter()
+            .map(|argument| parse_number(argument))
+            .collect::<Result<Vec<u16>, String>>()
+        {
+            Ok(numbers) => numbers,
+            Err(message) => {
+                writeln!(output, "{message}")?;
+                return Ok(false);
+            }
+        };
+
+        match (command, &numbers[..]) {
+            ("q" | "quit", []) => return Ok(true),
+            ("h" | "help", []) => write!(output, "{HELP_TEXT}")?,
+            ("b" | "break", [address]) => {
+                self.add_breakpoint(*address);
+                writeln!(output, "Added breakpoint at {address:04X}")?;
+            }
+            ("d" | "delete", [address]) => {
+                if self.remove_breakpoint(*address) {
+                    writeln!(output, "Removed breakpoint at {address:04X}")?;
+                } else {
+                    writeln!(output, "No breakpoint at {address:04X}")?;
+                }
+            }
+            ("bl" | "breakpoints", []) => {
+                for address in self.breakpoints() {
+                    writeln!(output, "{address:04X}")?;
+                }
+            }
+            ("w" | "watch", [start_address] | [start_address, _]) => {
+                let end_address = numbers.get(1).copied().unwrap_or(*start_address);
+                add_watchpoint(state, *start_address, end_address, WatchKind::Write, output)?;
+            }
+            ("rw" | "rwatch", [start_address] | [start_address, _]) => {
+                let end_address = numbers.get(1).copied().unwrap_or(*start_address);
+                add_watchpoint(state, *start_address, end_address, WatchKind::Read, output)?;
+            }
+            ("aw" | "awatch", [start_address] | [start_address, _]) => {
+                let end_address = numbers.get(1).copied().unwrap_or(*start_address);
+                add_watchpoint(
+                    state,
+                    *start_address,
+                    end_address,
+                    WatchKind::ReadWrite,
+                    output,
+                )?;
+            }
+            ("dw" | "delete-watch", [address]) => {
+                if state.watchpoints.remove_watchpoints_at(*address) {
+                    writeln!(output, "Removed watchpoints covering {address:04X}")?;
+                } else {
+                    writeln!(output, "No watchpoints covering {address:04X}")?;
+                }
+            }
+            ("bi" | "break-in", [port_number]) => {
+                add_port_breakpoint(state, *port_number, PortDirection::In, output)?;
+            }
+            ("bo" | "break-out", [port_number]) => {
+                add_port_breakpoint(state, *port_number, PortDirection::Out, output)?;
+            }
+            ("dbi" | "delete-in", [port_number]) => {
+                remove_port_breakpoint(state, *port_number, PortDirection::In, output)?;
+            }
+            ("dbo" | "delete-out", [port_number]) => {
+                remove_port_breakpoint(state, *port_number, PortDirection::Out, output)?;
+            }
+            ("wl" | "watchpoints", []) => {
+                for watchpoint in state.watchpoints.watchpoints() {
+                    writeln!(
+                        output,
+                        "{:04X}-{:04X} {:?}",
+                        watchpoint.addresses.start(),
+                        watchpoint.addresses.end(),
+                        watchpoint.kind
+                    )?;
+                }
+                for port_breakpoint in state.watchpoints.port_breakpoints() {
+                    writeln!(
+                        output,
+                        "Port {:02X} {:?}",
+                        port_breakpoint.port_number, port_breakpoint.direction
+                    )?;
+                }
+            }
+            ("s" | "step", [] | [_]) => {
+                let count = numbers.first().copied().unwrap_or(1);
+                let mut reason = StopReason::Stepped;
+                for _ in 0..count {
+                    reason = self.step(state);
+                    if reason != StopReason::Stepped {
+                        break;
+                    }
+                }
+                self.write_stop(state, reason, output)?;
+            }
+            ("n" | "next", []) => {
+                let reason = self.step_over(state);
+                self.write_stop(state, reason, output)?;
+            }
+            ("f" | "finish", []) => {
+                let reason = self.run_until_return(state);
+                self.write_stop(state, reason, output)?;
+            }
+            ("c" | "continue", [] | [_]) => {
+                let frame_limit = numbers.first().copied().unwrap_or(DEFAULT_FRAME_LIMIT);
+                let reason = self.continue_execution(state, frame_limit);
+                self.write_stop(state, reason, output)?;
+            }
+            ("r" | "registers", []) => writeln!(output, "{}", format_registers(state))?,
+            ("m" | "memory", [address] | [address, _]) => {
+                let length = numbers
+                    .get(1)
+                    .copied()
+                    .unwrap_or(DEFAULT_MEMORY_DUMP_LENGTH);
+                write!(output, "{}", format_memory(state, *address, length))?;
+            }
+            ("e" | "edit", [address, values @ ..]) if !values.is_empty() => {
+                for (offset, value) in values.iter().enumerate() {
+                    let Ok(value) = u8::try_from(*value) else {
+                        writeln!(output, "Value {value:X} does not fit in a byte")?;
+                        return Ok(false);
+                    };
+                    state
+                        .memory
+                        .poke(address.wrapping_add(offset as u16), value);
+                }
+            }
+            ("l" | "list", [] | [_] | [_, _]) => {
+                let address = numbers.first().copied().unwrap_or(state.program_counter);
+                let count = numbers.get(1).copied().unwrap_or(DEFAULT_LIST_COUNT);
+                write!(output, "{}", format_disassembly(state, address, count))?;
+            }
+            _ => writeln!(
+                output,
+                "Unknown command or wrong arguments: {command_line} (type 'help' for a list of commands)"
+            )?,
+        }
+
+        Ok(false)
+    }
+
+    fn write_stop(
+        &self,
+        state: &State,
+        reason: StopReason,
+        output: &mut dyn Write,
+    ) -> io::Result<()> {
+        if reason != StopReason::Stepped {
+            writeln!(output, "{reason}")?;
+        }
+        write!(
+            output,
+            "{}",
+            format_disassembly(state, state.program_counter, 1)
+        )
+    }
+}
+
+fn add_watchpoint(
+    state: &mut State,
+    start_address: u16,
+    end_address: u16,
+    kind: WatchKind,
+    output: &mut dyn Write,
+) -> io::Result<()> {
+    if end_address < start_address {
+        return writeln!(
+            output,
+            "The end address must not be before the start address"
+        );
+    }
+
+    state
+        .watchpoints
+        .add_watchpoint(start_address..=end_address, kind);
+    writeln!(
+        output,
+        "Added {kind:?} watchpoint at {start_address:04X}-{end_address:04X}"
+    )
+}
+
+fn add_port_breakpoint(
+    state: &mut State,
+    port_number: u16,
+    direction: PortDirection,
+    output: &mut dyn Write,
+) -> io::Result<()> {
+    let Ok(port_number) = u8::try_from(port_number) else {
+        return writeln!(output, "Port {port_number:X} does not fit in a byte");
+    };
+
+    state
+        .watchpoints
+        .add_port_breakpoint(port_number, direction);
+    writeln!(
+        output,
+        "Added breakpoint on {direction:?} port {port_number:02X}"
+    )
+}
+
+fn remove_port_breakpoint(
+    state: &mut State,
+    port_number: u16,
+    direction: PortDirection,
+    output: &mut dyn Write,
+) -> io::Result<()> {
+    let is_removed = u8::try_from(port_number).is_ok_and(|port_number| {
+        state
+            .watchpoints
+            .remove_port_breakpoint(port_number, direction)
+    });
+
+    if is_removed {
+        writeln!(
+            output,
+            "Removed breakpoint on {direction:?} port {port_number:02X}"
+        )
+    } else {
+        writeln!(
+            output,
+            "No breakpoint on {direction:?} port {port_number:02X}"
+        )
+    }
+}
+
+const HELP_TEXT: &str = "\
+Addresses and values are in hexadecimal.
+  b, break <address>             Add a breakpoint
+  d, delete <address>            Remove a breakpoint
+  bl, breakpoints                List breakpoints
+  w, watch <start> [end]         Stop after memory in a range is written
+  rw, rwatch <start> [end]       Stop after memory in a range is read
+  aw, awatch <start> [end]       Stop after memory in a range is read or written
+  dw, delete-watch <address>     Remove the watchpoints covering an address
+  bi, break-in <port>            Stop after an IN from a port
+  bo, break-out <port>           Stop after an OUT to a port
+  dbi, delete-in <port>          Remove an IN port breakpoint
+  dbo, delete-out <port>         Remove an OUT port breakpoint
+  wl, watchpoints                List watchpoints and port breakpoints
+  s, step [count]                Run the next instruction(s)
+  n, next                        Step, running over any CALL or RST
+  f, finish                      Run until the current subroutine returns
+  c, continue [frames]           Run until a breakpoint or watchpoint is hit, the CPU halts with
+                                 interrupts disabled, or the frame limit (default 100h) is reached
+  r, registers                   Show registers and flags
+  m, memory <address> [length]   Show a hexdump of memory
+  e, edit <address> <value>...   Write bytes to memory
+  l, list [address] [count]      Disassemble from an address (defaults to PC)
+  h, help                        Show this help
+  q, quit                        Quit the debugger
+";
+
+fn parse_number(text: &str) -> Result<u16, String> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_suffix(['h', 'H']))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid hexadecimal number {text}"))
+}
+
+pub fn format_registers(state: &State) -> String {
+    let flags: String = [
+        (ConditionFlag::Sign, 'S'),
+        (ConditionFlag::Zero, 'Z'),
+        (ConditionFlag::AuxiliaryCarry, 'A'),
+        (ConditionFlag::Parity, 'P'),
+        (ConditionFlag::Carry, 'C'),
+    ]
+    .iter()
+    .map(|(condition_flag, letter)| {
+        if state.condition_flags[*condition_flag] {
+            *letter
+        } else {
+            '-'
+        }
+    })
+    .collect();
+
+    format!(
+        "PC: {:04X}  SP: {:04X}  A: {:02X}  BC: {:04X}  DE: {:04X}  HL: {:04X}  Flags: {flags}  \
+        Interrupts: {}  Halted: {}  CYC: {}",
+        state.program_counter,
+        state.stack_pointer,
+        state.registers[Register::A],
+        state.full_rp_value(RegisterPair::BC),
+        state.full_rp_value(RegisterPair::DE),
+        state.full_rp_value(RegisterPair::HL),
+        if state.are_interrupts_enabled {
+            "on"
+        } else {
+            "off"
+        },
+        if state.is_halted { "yes" } else { "no" },
+        state.cpu_total_state_count(),
+    )
+}
+
+pub fn format_memory(state: &State, start_address: u16, length: u16) -> String {
+    let mut memory_dump = String::new();
+
+    for row_offset in (0..length).step_by(usize::from(MEMORY_DUMP_ROW_LENGTH)) {
+        let row_address = start_address.wrapping_add(row_offset);
+        let row_length = MEMORY_DUMP_ROW_LENGTH.min(length - row_offset);
+        let row_bytes: Vec<u8> = (0..row_length)
+            .map(|offset| state.memory.read(row_address.wrapping_add(offset)))
+            .collect();
+        let hex_text = row_bytes
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ascii_text: String = row_bytes
+            .iter()
+            .map(|byte| {
+                if byte.is_ascii_graphic() || *byte == b' ' {
+                    *byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        memory_dump += &format!("{row_address:04X}  {hex_text:<47}  {ascii_text}\n");
+    }
+
+    memory_dump
+}
+
+pub fn format_disassembly(state: &State, start_address: u16, count: u16) -> String {
+    let mut disassembly = String::new();
+    let mut address = start_address;
+
+    for _ in 0..count {
+        let instruction = disassembler::disassemble_state_instruction(state, address);
+        let marker = if address == state.program_counter {
+            ">"
+        } else {
+            " "
+        };
+        disassembly += &format!(
+            "{marker} {}\n",
+            disassembler::format_listing_line(
+                address,
+                &instruction.bytes,
+                &instruction.to_string()
+            )
+        );
+        address = instruction.next_address();
+    }
+
+    disassembly
+}
+
+pub fn run_repl(
+    state: &mut State,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> io::Result<()> {
+    let mut debugger = Debugger::default();
+    let mut command_line = String::new();
+
+    writeln!(output, "Type 'help' for a list of commands")?;
+    write!(
+        output,
+        "{}",
+        format_disassembly(state, state.program_counter, 1)
+    )?;
+
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+
+        command_line.clear();
+        if input.read_line(&mut command_line)? == 0 {
+            return Ok(());
+        }
+
+        if debugger.execute_command(state, command_line.trim(), output)? {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler;
+
+    fn state_from_source(source: &str) -> State {
+        let mut state = State::default();
+        state.load_memory(&assembler::assemble(source).unwrap());
+        state
+    }
+
+    fn command_output(debugger: &mut Debugger, state: &mut State, command_line: &str) -> String {
+        let mut output = Vec::new();
+        debugger
+            .execute_command(state, command_line, &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    const PROGRAM: &str = "
+        LXI SP,0100h
+        CALL SUB
+        MVI B,2
+        HLT
+        SUB: MVI A,1
+        RET";
+
+    #[test]
+    fn step_runs_a_single_instruction() {
+        let mut state = state_from_source(PROGRAM);
+        let debugger = Debugger::default();
+        assert_eq!(debugger.step(&mut state), StopReason::Stepped);
+        assert_eq!(state.stack_pointer, 0x0100);
+        assert_eq!(debugger.step(&mut state), StopReason::Stepped);
+        assert_eq!(state.program_counter, 0x0009);
+    }
+
+    #[test]
+    fn step_over_runs_a_whole_subroutine() {
+        let mut state = state_from_source(PROGRAM);
+        let debugger = Debugger::default();
+        debugger.step(&mut state);
+        assert_eq!(debugger.step_over(&mut state), StopReason::Stepped);
+        assert_eq!(state.program_counter, 0x0006);
+        assert_eq!(state.registers[Register::A], 1);
+    }
+
+    #[test]
+    fn step_over_stops_at_breakpoints_inside_the_subroutine() {
+        let mut state = state_from_source(PROGRAM);
+        let mut debugger = Debugger::default();
+        debugger.add_breakpoint(0x000B);
+        debugger.step(&mut state);
+        assert_eq!(
+            debugger.step_over(&mut state),
+            StopReason::Breakpoint(0x000B)
+        );
+    }
+
+    #[test]
+    fn run_until_return_stops_after_the_return() {
+        let mut state = state_from_source(PROGRAM);
+        let debugger = Debugger::default();
+        debugger.step(&mut state);
+        debugger.step(&mut state);
+        assert_eq!(debugger.run_until_return(&mut state), StopReason::Returned);
+        assert_eq!(state.program_counter, 0x0006);
+        assert_eq!(state.stack_pointer, 0x0100);
+    }
+
+    #[test]
+    fn continue_runs_until_a_breakpoint_or_halt() {
+        let mut state = state_from_source(PROGRAM);
+        let mut debugger = Debugger::default();
+        debugger.add_breakpoint(0x0006);
+        assert_eq!(
+            debugger.continue_execution(&mut state, DEFAULT_FRAME_LIMIT),
+            StopReason::Breakpoint(0x0006)
+        );
+        assert_eq!(
+            debugger.continue_execution(&mut state, DEFAULT_FRAME_LIMIT),
+            StopReason::Halted
+        );
+        assert_eq!(state.registers[Register::B], 2);
+    }
+
+    #[test]
+    fn continue_raises_video_interrupts() {
+        let mut state = state_from_source(
+            "
+            LXI SP,2400h
+            EI
+            LOOP: JMP LOOP
+            ORG 08h
+            EI
+            RET
+            ORG 10h
+            HLT",
+        );
+        let mut debugger = Debugger::default();
+        debugger.add_breakpoint(0x0010);
+
+        assert_eq!(
+            debugger.continue_execution(&mut state, DEFAULT_FRAME_LIMIT),
+            StopReason::Breakpoint(0x0010)
+        );
+        // RST 2 is raised at the end of the first JMP to reach the vertical blank
+        let vblank_state_count = scheduler::scan_line_state_count(0, 224);
+        assert!(state.cpu_total_state_count() >= vblank_state_count);
+        assert!(state.cpu_total_state_count() < vblank_state_count + 10);
+    }
+
+    #[test]
+    fn continue_stops_at_the_frame_limit() {
+        let mut state = state_from_source("LOOP: JMP LOOP");
+        let mut debugger = Debugger::default();
+
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "c 2"),
+            "Stopped at the frame limit\n> 0000  C3 00 00  JMP 0000h\n"
+        );
+        assert!(state.cpu_total_state_count() >= scheduler::frame_start_state_count(2));
+        assert!(state.cpu_total_state_count() < scheduler::frame_start_state_count(2) + 10);
+    }
+
+    #[test]
+    fn continue_stops_after_a_watched_write_or_port_access() {
+        let mut state = state_from_source(
+            "
+            LXI H,2000h
+            MVI M,7
+            OUT 3
+            HLT",
+        );
+        let mut debugger = Debugger::default();
+
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "w 1ff0 200f"),
+            "Added Write watchpoint at 1FF0-200F\n"
+        );
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "bo 3"),
+            "Added breakpoint on Out port 03\n"
+        );
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "c"),
+            "Hit watchpoint: Wrote 07 to 2000 (PC: 0003)\n> 0005  D3 03     OUT 03h\n"
+        );
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "c"),
+            "Hit port breakpoint: Wrote 00 to port 03 (PC: 0005)\n> 0007  76        HLT\n"
+        );
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "dbo 3"),
+            "Removed breakpoint on Out port 03\n"
+        );
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "wl"),
+            "1FF0-200F Write\n"
+        );
+    }
+
+    #[test]
+    fn commands_show_and_edit_state() {
+        let mut state = state_from_source(PROGRAM);
+        let mut debugger = Debugger::default();
+
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "s 2"),
+            "> 0009  3E 01     MVI A,01h\n"
+        );
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "r"),
+            "PC: 0009  SP: 00FE  A: 00  BC: 0000  DE: 0000  HL: 0000  Flags: -----  \
+            Interrupts: off  Halted: no  CYC: 27\n"
+        );
+        assert_eq!(command_output(&mut debugger, &mut state, "e 20 48 49"), "");
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "m 0x20 4"),
+            format!("0020  48 49 00 00{}  HI..\n", " ".repeat(36))
+        );
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "list 3 2"),
+            "  0003  CD 09 00  CALL 0009h\n  0006  06 02     MVI B,02h\n"
+        );
+        assert_eq!(
+            command_output(&mut debugger, &mut state, "m zz"),
+            "Invalid hexadecimal number zz\n"
+        );
+    }
+
+    #[test]
+    fn repl_runs_commands_until_quit() {
+        let mut state = state_from_source(PROGRAM);
+        let mut input = "b 6\nc\nq\nr\n".as_bytes();
+        let mut output = Vec::new();
+        run_repl(&mut state, &mut input, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Added breakpoint at 0006"));
+        assert!(output.contains("Hit breakpoint at 0006\n> 0006  06 02     MVI B,02h"));
+        assert!(!output.contains("PC:"));
+    }
+}
@@ -1,7 +1,12 @@
-use crate::{ConditionFlag, Operation, Register, RegisterPair};
+use crate::{
+    Condition, ConditionFlag, InstructionDataType, Operation, Register, RegisterPair, State,
+    bit_operations,
+};
 // #[cfg(test)]
 // use mutagen::mutate;
 
+// Every op code decodes, the undocumented ones included, and the match has no fallback arm so the
+// compiler checks that. Unsupported operands are reported as CPU errors when the operation runs.
 #[allow(clippy::unusual_byte_groupings)]
 // #[cfg_attr(test, mutate)]
 pub fn disassemble_op_code(op_code: u8) -> Operation {
@@ -199,6 +204,8 @@ pub fn disassemble_op_code(op_code: u8) -> Operation {
         0b00_111_111 => Operation::Cmc,
         0b00_110_111 => Operation::Stc,
         0b11_000_011 => Operation::Jmp,
+        // Undocumented alias for JMP
+        0b11_001_011 => Operation::Jmp,
         0b11_000_010 => Operation::Jcond((ConditionFlag::Zero, false)),
         0b11_001_010 => Operation::Jcond((ConditionFlag::Zero, true)),
         0b11_010_010 => Operation::Jcond((ConditionFlag::Carry, false)),
@@ -208,6 +215,8 @@ pub fn disassemble_op_code(op_code: u8) -> Operation {
         0b11_110_010 => Operation::Jcond((ConditionFlag::Sign, false)),
         0b11_111_010 => Operation::Jcond((ConditionFlag::Sign, true)),
         0b11_001_101 => Operation::Call,
+        // Undocumented aliases for CALL
+        0b11_011_101 | 0b11_101_101 | 0b11_111_101 => Operation::Call,
         0b11_000_100 => Operation::Ccond((ConditionFlag::Zero, false)),
         0b11_001_100 => Operation::Ccond((ConditionFlag::Zero, true)),
         0b11_010_100 => Operation::Ccond((ConditionFlag::Carry, false)),
@@ -217,6 +226,8 @@ pub fn disassemble_op_code(op_code: u8) -> Operation {
         0b11_110_100 => Operation::Ccond((ConditionFlag::Sign, false)),
         0b11_111_100 => Operation::Ccond((ConditionFlag::Sign, true)),
         0b11_001_001 => Operation::Ret,
+        // Undocumented alias for RET
+        0b11_011_001 => Operation::Ret,
         0b11_000_000 => Operation::Rcond((ConditionFlag::Zero, false)),
         0b11_001_000 => Operation::Rcond((ConditionFlag::Zero, true)),
         0b11_010_000 => Operation::Rcond((ConditionFlag::Carry, false)),
@@ -250,7 +261,298 @@ pub fn disassemble_op_code(op_code: u8) -> Operation {
         0b11_110_011 => Operation::Di,
         0b01_110_110 => Operation::Hlt,
         0b00_000_000 => Operation::Nop,
-        _ => panic!("Unrecognized opcode: {op_code:#010b}"),
+        // Undocumented aliases for NOP
+        0b00_001_000 | 0b00_010_000 | 0b00_011_000 | 0b00_100_000 | 0b00_101_000 | 0b00_110_000
+        | 0b00_111_000 => Operation::Nop,
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Instruction {
+    pub address: u16,
+    pub operation: Operation,
+    pub bytes: Vec<u8>,
+}
+
+impl Instruction {
+    pub fn length(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    pub fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.length())
+    }
+
+    pub fn data(&self) -> Option<u16> {
+        match self.operation.additional_data_required() {
+            InstructionDataType::None => None,
+            InstructionDataType::Single => Some(u16::from(self.bytes[1])),
+            InstructionDataType::LowHigh => Some(bit_operations::concat_low_high_bytes(
+                self.bytes[1],
+                self.bytes[2],
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mnemonic = mnemonic(&self.operation);
+        let data = self.data().map(|data| {
+            if self.operation.additional_data_required() == InstructionDataType::Single {
+                intel_hex_byte(data as u8)
+            } else {
+                intel_hex_word(data)
+            }
+        });
+
+        match (operands(&self.operation), data) {
+            (Some(operands), Some(data)) => write!(f, "{mnemonic} {operands},{data}"),
+            (Some(operands), None) => write!(f, "{mnemonic} {operands}"),
+            (None, Some(data)) => write!(f, "{mnemonic} {data}"),
+            (None, None) => write!(f, "{mnemonic}"),
+        }
+    }
+}
+
+pub fn instruction_length(operation: &Operation) -> u16 {
+    match operation.additional_data_required() {
+        InstructionDataType::None => 1,
+        InstructionDataType::Single => 2,
+        InstructionDataType::LowHigh => 3,
+    }
+}
+
+// Decodes the instruction at the given address of a byte slice that starts at the origin address,
+// returning None if the slice ends before all of the instruction's bytes.
+pub fn disassemble_instruction(bytes: &[u8], origin: u16, address: u16) -> Option<Instruction> {
+    let start_index = usize::from(address.checked_sub(origin)?);
+    let op_code = *bytes.get(start_index)?;
+    let operation = disassemble_op_code(op_code);
+    let end_index = start_index + usize::from(instruction_length(&operation));
+
+    Some(Instruction {
+        address,
+        operation,
+        bytes: bytes.get(start_index..end_index)?.to_vec(),
+    })
+}
+
+pub fn disassemble_state_instruction(state: &State, address: u16) -> Instruction {
+    let operation = disassemble_op_code(state.memory.read(address));
+    let bytes = (0..instruction_length(&operation))
+        .map(|offset| state.memory.read(address.wrapping_add(offset)))
+        .collect();
+
+    Instruction {
+        address,
+        operation,
+        bytes,
+    }
+}
+
+pub fn format_listing_line(address: u16, bytes: &[u8], text: &str) -> String {
+    let byte_text = bytes
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("{address:04X}  {byte_text:<8}  {text}")
+}
+
+pub fn format_data_bytes(bytes: &[u8]) -> String {
+    let byte_text = bytes
+        .iter()
+        .map(|byte| intel_hex_byte(*byte))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("DB {byte_text}")
+}
+
+pub fn disassemble_listing(bytes: &[u8], origin: u16) -> String {
+    let mut listing = String::new();
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let address = origin.wrapping_add(index as u16);
+
+        if let Some(instruction) = disassemble_instruction(bytes, origin, address) {
+            listing += &format_listing_line(address, &instruction.bytes, &instruction.to_string());
+            index += instruction.bytes.len();
+        } else {
+            // A truncated instruction at the end of the bytes can only be shown as data
+            let remaining_bytes = &bytes[index..];
+            listing += &format_listing_line(
+                address,
+                remaining_bytes,
+                &format_data_bytes(remaining_bytes),
+            );
+            index = bytes.len();
+        }
+
+        listing.push('\n');
+    }
+
+    listing
+}
+
+// Intel syntax requires hexadecimal numbers to start with a digit, so a leading zero is added if needed
+pub fn intel_hex_byte(value: u8) -> String {
+    intel_hex(format!("{value:02X}"))
+}
+
+pub fn intel_hex_word(value: u16) -> String {
+    intel_hex(format!("{value:04X}"))
+}
+
+fn intel_hex(digits: String) -> String {
+    if digits.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        format!("0{digits}h")
+    } else {
+        format!("{digits}h")
+    }
+}
+
+pub fn register_name(register: Register) -> &'static str {
+    match register {
+        Register::A => "A",
+        Register::B => "B",
+        Register::C => "C",
+        Register::D => "D",
+        Register::E => "E",
+        Register::H => "H",
+        Register::L => "L",
+    }
+}
+
+pub fn register_pair_name(register_pair: RegisterPair) -> &'static str {
+    match register_pair {
+        RegisterPair::BC => "B",
+        RegisterPair::DE => "D",
+        RegisterPair::HL => "H",
+        RegisterPair::SP => "SP",
+    }
+}
+
+pub fn condition_name(condition: Condition) -> &'static str {
+    match condition {
+        (ConditionFlag::Zero, false) => "NZ",
+        (ConditionFlag::Zero, true) => "Z",
+        (ConditionFlag::Carry, false) => "NC",
+        (ConditionFlag::Carry, true) => "C",
+        (ConditionFlag::Parity, false) => "PO",
+        (ConditionFlag::Parity, true) => "PE",
+        (ConditionFlag::Sign, false) => "P",
+        (ConditionFlag::Sign, true) => "M",
+        (ConditionFlag::AuxiliaryCarry, _) => "??",
+    }
+}
+
+pub fn mnemonic(operation: &Operation) -> String {
+    let mnemonic = match operation {
+        Operation::Mov(_, _) | Operation::MovFromMem(_) | Operation::MovToMem(_) => "MOV",
+        Operation::Mvi(_) | Operation::MviMem => "MVI",
+        Operation::Lxi(_) => "LXI",
+        Operation::Lda => "LDA",
+        Operation::Sta => "STA",
+        Operation::Lhld => "LHLD",
+        Operation::Shld => "SHLD",
+        Operation::Ldax(_) => "LDAX",
+        Operation::Stax(_) => "STAX",
+        Operation::Xchg => "XCHG",
+        Operation::Add(_) | Operation::AddMem => "ADD",
+        Operation::Adi => "ADI",
+        Operation::Adc(_) | Operation::AdcMem => "ADC",
+        Operation::Aci => "ACI",
+        Operation::Sub(_) | Operation::SubMem => "SUB",
+        Operation::Sui => "SUI",
+        Operation::Sbb(_) | Operation::SbbMem => "SBB",
+        Operation::Sbi => "SBI",
+        Operation::Inr(_) | Operation::InrMem => "INR",
+        Operation::Dcr(_) | Operation::DcrMem => "DCR",
+        Operation::Inx(_) => "INX",
+        Operation::Dcx(_) => "DCX",
+        Operation::Dad(_) => "DAD",
+        Operation::Daa => "DAA",
+        Operation::Ana(_) | Operation::AnaMem => "ANA",
+        Operation::Ani => "ANI",
+        Operation::Xra(_) | Operation::XraMem => "XRA",
+        Operation::Xri => "XRI",
+        Operation::Ora(_) | Operation::OraMem => "ORA",
+        Operation::Ori => "ORI",
+        Operation::Cmp(_) | Operation::CmpMem => "CMP",
+        Operation::Cpi => "CPI",
+        Operation::Rlc => "RLC",
+        Operation::Rrc => "RRC",
+        Operation::Ral => "RAL",
+        Operation::Rar => "RAR",
+        Operation::Cma => "CMA",
+        Operation::Cmc => "CMC",
+        Operation::Stc => "STC",
+        Operation::Jmp => "JMP",
+        Operation::Jcond(condition) => return format!("J{}", condition_name(*condition)),
+        Operation::Call => "CALL",
+        Operation::Ccond(condition) => return format!("C{}", condition_name(*condition)),
+        Operation::Ret => "RET",
+        Operation::Rcond(condition) => return format!("R{}", condition_name(*condition)),
+        Operation::Rst(_) => "RST",
+        Operation::Pchl => "PCHL",
+        Operation::Push(_) | Operation::PushPsw => "PUSH",
+        Operation::Pop(_) | Operation::PopPsw => "POP",
+        Operation::Xthl => "XTHL",
+        Operation::Sphl => "SPHL",
+        Operation::In => "IN",
+        Operation::Out => "OUT",
+        Operation::Ei => "EI",
+        Operation::Di => "DI",
+        Operation::Hlt => "HLT",
+        Operation::Nop => "NOP",
+    };
+    mnemonic.to_string()
+}
+
+pub fn operands(operation: &Operation) -> Option<String> {
+    match operation {
+        Operation::Mov(source_register, destination_register) => Some(format!(
+            "{},{}",
+            register_name(*destination_register),
+            register_name(*source_register)
+        )),
+        Operation::MovFromMem(register) => Some(format!("{},M", register_name(*register))),
+        Operation::MovToMem(register) => Some(format!("M,{}", register_name(*register))),
+        Operation::Mvi(register)
+        | Operation::Add(register)
+        | Operation::Adc(register)
+        | Operation::Sub(register)
+        | Operation::Sbb(register)
+        | Operation::Inr(register)
+        | Operation::Dcr(register)
+        | Operation::Ana(register)
+        | Operation::Xra(register)
+        | Operation::Ora(register)
+        | Operation::Cmp(register) => Some(register_name(*register).to_string()),
+        Operation::MviMem
+        | Operation::AddMem
+        | Operation::AdcMem
+        | Operation::SubMem
+        | Operation::SbbMem
+        | Operation::InrMem
+        | Operation::DcrMem
+        | Operation::AnaMem
+        | Operation::XraMem
+        | Operation::OraMem
+        | Operation::CmpMem => Some("M".to_string()),
+        Operation::Lxi(register_pair)
+        | Operation::Ldax(register_pair)
+        | Operation::Stax(register_pair)
+        | Operation::Inx(register_pair)
+        | Operation::Dcx(register_pair)
+        | Operation::Dad(register_pair)
+        | Operation::Push(register_pair)
+        | Operation::Pop(register_pair) => Some(register_pair_name(*register_pair).to_string()),
+        Operation::PushPsw | Operation::PopPsw => Some("PSW".to_string()),
+        Operation::Rst(reset_index) => Some(reset_index.to_string()),
+        _ => None,
     }
 }
 
@@ -258,7 +560,7 @@ pub fn disassemble_op_code(op_code: u8) -> Operation {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Condition;
+    use maplit::hashmap;
     use std::collections::HashMap;
 
     fn register_from_bit_pattern(bit_pattern: u8) -> Register {
@@ -374,9 +676,14 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Unrecognized opcode: 0b00001000")]
-    fn disassembler_panics_on_unsupported_op_code() {
-        disassemble_op_code(0b00_001_000);
+    fn disassembler_handles_every_op_code() {
+        for op_code in 0..=u8::MAX {
+            let operation = disassemble_op_code(op_code);
+            assert!(
+                operation.machine_states(&State::default()) > 0,
+                "Expected operation {operation:?} for opcode {op_code:#010b} to take some states",
+            );
+        }
     }
 
     #[test]
@@ -930,4 +1237,129 @@ mod tests {
         let operation = disassemble_op_code(0b00_000_000);
         assert_operation_equals_expected(&operation, &Operation::Nop);
     }
+
+    #[test]
+    fn disassembler_handles_undocumented_nop_aliases() {
+        let alias_map = all_combinations_for_op_codes(
+            0b00_000_000,
+            3,
+            vec![0b001, 0b010, 0b011, 0b100, 0b101, 0b110, 0b111],
+            |_| Operation::Nop,
+        );
+
+        for (op_code, expected_operation) in alias_map {
+            let operation = disassemble_op_code(op_code);
+            assert_operation_equals_expected(&operation, &expected_operation);
+        }
+    }
+
+    #[test]
+    fn disassembler_handles_undocumented_jmp_alias() {
+        let operation = disassemble_op_code(0b11_001_011);
+        assert_operation_equals_expected(&operation, &Operation::Jmp);
+    }
+
+    #[test]
+    fn disassembler_handles_undocumented_ret_alias() {
+        let operation = disassemble_op_code(0b11_011_001);
+        assert_operation_equals_expected(&operation, &Operation::Ret);
+    }
+
+    #[test]
+    fn disassembler_handles_undocumented_call_aliases() {
+        let alias_map =
+            all_combinations_for_op_codes(0b11_001_101, 4, vec![0b01, 0b10, 0b11], |_| {
+                Operation::Call
+            });
+
+        for (op_code, expected_operation) in alias_map {
+            let operation = disassemble_op_code(op_code);
+            assert_operation_equals_expected(&operation, &expected_operation);
+        }
+    }
+
+    fn assert_instruction_text_is_as_expected(bytes: &[u8], expected_text: &str) {
+        let instruction = disassemble_instruction(bytes, 0x0000, 0x0000).unwrap();
+        assert_eq!(instruction.to_string(), expected_text);
+        assert_eq!(instruction.length() as usize, bytes.len());
+    }
+
+    #[test]
+    fn instructions_are_formatted_as_intel_mnemonics() {
+        assert_instruction_text_is_as_expected(&[0x00], "NOP");
+        assert_instruction_text_is_as_expected(&[0x78], "MOV A,B");
+        assert_instruction_text_is_as_expected(&[0x7E], "MOV A,M");
+        assert_instruction_text_is_as_expected(&[0x77], "MOV M,A");
+        assert_instruction_text_is_as_expected(&[0x3E, 0x3E], "MVI A,3Eh");
+        assert_instruction_text_is_as_expected(&[0x36, 0xFF], "MVI M,0FFh");
+        assert_instruction_text_is_as_expected(&[0x21, 0x00, 0x24], "LXI H,2400h");
+        assert_instruction_text_is_as_expected(&[0x31, 0x00, 0xF0], "LXI SP,0F000h");
+        assert_instruction_text_is_as_expected(&[0x1A], "LDAX D");
+        assert_instruction_text_is_as_expected(&[0x86], "ADD M");
+        assert_instruction_text_is_as_expected(&[0xC2, 0x2B, 0x1A], "JNZ 1A2Bh");
+        assert_instruction_text_is_as_expected(&[0xFC, 0x34, 0x12], "CM 1234h");
+        assert_instruction_text_is_as_expected(&[0xE8], "RPE");
+        assert_instruction_text_is_as_expected(&[0xCF], "RST 1");
+        assert_instruction_text_is_as_expected(&[0xF5], "PUSH PSW");
+        assert_instruction_text_is_as_expected(&[0xC1], "POP B");
+        assert_instruction_text_is_as_expected(&[0xDB, 0x01], "IN 01h");
+        assert_instruction_text_is_as_expected(&[0xD3, 0x06], "OUT 06h");
+    }
+
+    #[test]
+    fn disassemble_instruction_returns_none_for_a_truncated_instruction() {
+        assert_eq!(
+            disassemble_instruction(&[0x00, 0xC3, 0x00], 0x0000, 0x0001),
+            None
+        );
+        assert_eq!(disassemble_instruction(&[0x00], 0x0100, 0x0000), None);
+    }
+
+    #[test]
+    fn disassemble_instruction_uses_the_origin_address() {
+        let instruction = disassemble_instruction(&[0x00, 0x06, 0x42], 0x0100, 0x0101).unwrap();
+        assert_eq!(instruction.address, 0x0101);
+        assert_eq!(instruction.operation, Operation::Mvi(Register::B));
+        assert_eq!(instruction.data(), Some(0x42));
+        assert_eq!(instruction.next_address(), 0x0103);
+    }
+
+    #[test]
+    fn disassemble_state_instruction_wraps_around_the_end_of_memory() {
+        let state = crate::StateBuilder::default()
+            .memory_values(hashmap! { 0xFFFF => 0xC3, 0x0000 => 0x34, 0x0001 => 0x12 })
+            .build();
+        let instruction = disassemble_state_instruction(&state, 0xFFFF);
+        assert_eq!(instruction.to_string(), "JMP 1234h");
+        assert_eq!(instruction.next_address(), 0x0002);
+    }
+
+    #[test]
+    fn disassemble_listing_shows_addresses_bytes_and_mnemonics() {
+        let listing = disassemble_listing(&[0x00, 0xC3, 0xD4, 0x18, 0x3E], 0x0000);
+        assert_eq!(
+            listing,
+            "0000  00        NOP\n\
+             0001  C3 D4 18  JMP 18D4h\n\
+             0004  3E        DB 3Eh\n"
+        );
+    }
+
+    #[test]
+    fn undocumented_aliases_take_the_same_states_as_their_documented_op_codes() {
+        let state = State::default();
+        let alias_map = hashmap! {
+            0b00_001_000 => 0b00_000_000,
+            0b11_001_011 => 0b11_000_011,
+            0b11_011_001 => 0b11_001_001,
+            0b11_011_101 => 0b11_001_101,
+        };
+
+        for (alias_op_code, op_code) in alias_map {
+            assert_eq!(
+                disassemble_op_code(alias_op_code).machine_states(&state),
+                disassemble_op_code(op_code).machine_states(&state),
+            );
+        }
+    }
 }
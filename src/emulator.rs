@@ -0,0 +1,295 @@
+use crate::machine::{Input, Machine, TimedAudioEvent};
+use crate::{CpuStop, bit_operations, scheduler};
+
+pub const ORIGINAL_SCREEN_WIDTH: u32 = 256;
+pub const ORIGINAL_SCREEN_HEIGHT: u32 = 224;
+pub const NUM_PIXEL_COMPONENTS: usize = 3;
+const VIDEO_MEMORY_START: u16 = 0x2400;
+
+pub struct Emulator {
+    machine: Box<dyn Machine>,
+}
+
+impl Emulator {
+    pub fn new(machine: Box<dyn Machine>) -> Self {
+        Emulator { machine }
+    }
+
+    pub fn machine(&self) -> &dyn Machine {
+        &*self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut dyn Machine {
+        &mut *self.machine
+    }
+
+    pub fn set_input(&mut self, input: Input, is_pressed: bool) {
+        self.machine.set_input(input, is_pressed);
+    }
+
+    pub fn run_frame(&mut self) -> Result<(), CpuStop> {
+        self.machine.set_ports_from_inputs();
+        scheduler::run_frame(self.machine.state_mut())
+    }
+
+    pub fn audio_events(&mut self) -> Vec<TimedAudioEvent> {
+        self.machine.take_audio_events()
+    }
+
+    pub fn screen_dimensions(&self) -> (u32, u32) {
+        if self.is_screen_rotated() {
+            (ORIGINAL_SCREEN_HEIGHT, ORIGINAL_SCREEN_WIDTH)
+        } else {
+            (ORIGINAL_SCREEN_WIDTH, ORIGINAL_SCREEN_HEIGHT)
+        }
+    }
+
+    // RGB24 pixels, row by row, with the machine's orientation and colours applied
+    pub fn framebuffer(&self) -> Vec<u8> {
+        let (screen_width, screen_height) = self.screen_dimensions();
+        let num_bytes_per_original_row = ORIGINAL_SCREEN_WIDTH / 8;
+        let mut framebuffer =
+            vec![0b0000_0000; (screen_width * screen_height) as usize * NUM_PIXEL_COMPONENTS];
+        let state = self.machine.state();
+
+        for original_screen_row in 0..ORIGINAL_SCREEN_HEIGHT {
+            for original_screen_column_byte in 0..num_bytes_per_original_row {
+                let memory_address = VIDEO_MEMORY_START
+                    + (original_screen_row * num_bytes_per_original_row
+                        + original_screen_column_byte) as u16;
+                let memory_value = state.memory.read(memory_address);
+
+                if memory_value != 0b0000_0000 {
+                    self.set_original_column_byte_pixels(
+                        &mut framebuffer,
+                        original_screen_column_byte,
+                        original_screen_row,
+                        memory_value,
+                    );
+                }
+            }
+        }
+
+        framebuffer
+    }
+
+    fn is_screen_rotated(&self) -> bool {
+        (self.machine.orientation() % 360 / 90) % 2 == 1
+    }
+
+    fn set_original_column_byte_pixels(
+        &self,
+        framebuffer: &mut [u8],
+        original_screen_column_byte: u32,
+        original_screen_row: u32,
+        memory_value: u8,
+    ) {
+        let (screen_width, screen_height) = self.screen_dimensions();
+        let num_screen_turns = self.machine.orientation() % 360 / 90;
+
+        for bit_index in 0_u8..=7_u8 {
+            if bit_operations::is_bit_set(memory_value, bit_index) {
+                let original_screen_column = original_screen_column_byte * 8 + u32::from(bit_index);
+
+                let (x, y) = match num_screen_turns {
+                    0 => (original_screen_column, original_screen_row),
+                    1 => (
+                        (screen_width - 1) - original_screen_row,
+                        original_screen_column,
+                    ),
+                    2 => (
+                        (screen_width - 1) - original_screen_column,
+                        (screen_height - 1) - original_screen_row,
+                    ),
+                    3 => (
+                        original_screen_row,
+                        (screen_height - 1) - original_screen_column,
+                    ),
+                    _ => unreachable!(),
+                };
+
+                let index = (y * screen_width + x) as usize * NUM_PIXEL_COMPONENTS;
+                let color = self.machine.pixel_color(x, y);
+                framebuffer[index..(index + NUM_PIXEL_COMPONENTS)]
+                    .copy_from_slice(&[color.r, color.g, color.b]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{AudioEvent, Color, SoundName};
+    use crate::space_invaders::SpaceInvadersMachine;
+    use crate::{CpuError, Ports, State, assembler};
+
+    #[derive(Default)]
+    struct TestPorts {
+        in_port_1: u8,
+    }
+
+    impl Ports for TestPorts {
+        fn read_in_port(&self, _port_number: u8) -> Result<u8, CpuError> {
+            Ok(self.in_port_1)
+        }
+        fn write_out_port(&mut self, _port_number: u8, _value: u8) -> Result<(), CpuError> {
+            Ok(())
+        }
+        fn in_port_static_value(&self, _port_number: u8) -> Option<u8> {
+            Some(self.in_port_1)
+        }
+        fn set_in_port_static_value(&mut self, _port_number: u8, value: u8) {
+            self.in_port_1 = value;
+        }
+    }
+
+    struct TestMachine {
+        state: State,
+        orientation: u32,
+        is_coin_inserted: bool,
+    }
+
+    impl TestMachine {
+        fn from_source(source: &str, orientation: u32) -> Self {
+            let mut state = crate::StateBuilder::default().build();
+            state.ports = Box::<TestPorts>::default();
+            state.load_memory(&assembler::assemble(source).unwrap());
+            TestMachine {
+                state,
+                orientation,
+                is_coin_inserted: false,
+            }
+        }
+    }
+
+    impl Machine for TestMachine {
+        fn state(&self) -> &State {
+            &self.state
+        }
+        fn state_mut(&mut self) -> &mut State {
+            &mut self.state
+        }
+        fn set_input(&mut self, input: Input, is_pressed: bool) {
+            if input == Input::Coin {
+                self.is_coin_inserted = is_pressed;
+            }
+        }
+        fn set_ports_from_inputs(&mut self) {
+            self.state
+                .ports
+                .set_in_port_static_value(1, u8::from(self.is_coin_inserted));
+        }
+        fn pixel_color(&self, _x: u32, y: u32) -> Color {
+            if y < 8 { Color::RED } else { Color::WHITE }
+        }
+        fn orientation(&self) -> u32 {
+            self.orientation
+        }
+        fn name(&self) -> &str {
+            "Test"
+        }
+    }
+
+    const TOP_LEFT_PIXEL_PROGRAM: &str = "
+        MVI A,01h
+        STA 2400h
+        HLT";
+
+    fn pixel(emulator: &Emulator, x: u32, y: u32) -> [u8; 3] {
+        let (screen_width, _) = emulator.screen_dimensions();
+        let index = (y * screen_width + x) as usize * NUM_PIXEL_COMPONENTS;
+        emulator.framebuffer()[index..index + NUM_PIXEL_COMPONENTS]
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn run_frame_runs_one_frame_of_cycles() {
+        let mut emulator = Emulator::new(Box::new(TestMachine::from_source(
+            TOP_LEFT_PIXEL_PROGRAM,
+            0,
+        )));
+        emulator.run_frame().unwrap();
+        emulator.run_frame().unwrap();
+
+        assert_eq!(emulator.machine().state().cpu_total_state_count(), 66_666);
+        assert!(emulator.audio_events().is_empty());
+    }
+
+    #[test]
+    fn audio_events_are_timed_by_the_state_count_of_their_out_instruction() {
+        let mut machine = SpaceInvadersMachine::default();
+        machine.state_mut().load_memory(
+            &assembler::assemble(
+                "
+                MVI A,02h
+                OUT 3
+                MVI A,00h
+                OUT 3
+                MVI A,02h
+                OUT 3
+                HLT",
+            )
+            .unwrap(),
+        );
+        let mut emulator = Emulator::new(Box::new(machine));
+        emulator.run_frame().unwrap();
+
+        assert_eq!(
+            emulator.audio_events(),
+            vec![
+                TimedAudioEvent {
+                    state_count: 7,
+                    event: AudioEvent::Play(SoundName::Shoot)
+                },
+                TimedAudioEvent {
+                    state_count: 41,
+                    event: AudioEvent::Play(SoundName::Shoot)
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn framebuffer_draws_video_memory_with_the_machine_colours() {
+        let mut emulator = Emulator::new(Box::new(TestMachine::from_source(
+            TOP_LEFT_PIXEL_PROGRAM,
+            0,
+        )));
+        emulator.run_frame().unwrap();
+
+        assert_eq!(emulator.screen_dimensions(), (256, 224));
+        assert_eq!(emulator.framebuffer().len(), 256 * 224 * 3);
+        assert_eq!(pixel(&emulator, 0, 0), [255, 0, 0]);
+        assert_eq!(pixel(&emulator, 1, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn framebuffer_is_rotated_by_the_machine_orientation() {
+        let mut emulator = Emulator::new(Box::new(TestMachine::from_source(
+            TOP_LEFT_PIXEL_PROGRAM,
+            270,
+        )));
+        emulator.run_frame().unwrap();
+
+        assert_eq!(emulator.screen_dimensions(), (224, 256));
+        assert_eq!(pixel(&emulator, 0, 255), [255, 255, 255]);
+        assert_eq!(pixel(&emulator, 0, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn inputs_are_applied_to_the_ports_before_the_frame_runs() {
+        let mut emulator = Emulator::new(Box::new(TestMachine::from_source(
+            "
+            IN 1
+            STA 2400h
+            HLT",
+            0,
+        )));
+        emulator.set_input(Input::Coin, true);
+        emulator.run_frame().unwrap();
+
+        assert_eq!(emulator.machine().state().memory.read(0x2400), 0x01);
+    }
+}
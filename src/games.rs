@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use crate::boot_hill::{self, BootHillMachine};
+use crate::checksum;
+use crate::machine::{BlankMachine, Machine};
+use crate::rom_set::{RomFile, RomSet};
+use crate::space_invaders::{self, SpaceInvadersMachine};
+
+// How the game's inputs, shift register and sounds are wired to the CPU's ports
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PortLayout {
+    SpaceInvaders,
+    BootHill,
+    // Runs the game with nothing connected to its ports, so it can't be controlled
+    Unknown { orientation: u32 },
+}
+
+pub struct Game {
+    // The MAME short name, which is also the expected file name
+    pub name: &'static str,
+    pub title: &'static str,
+    pub port_layout: PortLayout,
+    pub rom_set: Option<&'static RomSet>,
+}
+
+pub const KNOWN_GAMES: [Game; 3] = [
+    Game {
+        name: "invaders",
+        title: "Space Invaders",
+        port_layout: PortLayout::SpaceInvaders,
+        rom_set: Some(&space_invaders::ROM_SET),
+    },
+    Game {
+        name: "boothill",
+        title: "Boot Hill",
+        port_layout: PortLayout::BootHill,
+        rom_set: Some(&boot_hill::ROM_SET),
+    },
+    Game {
+        name: "lagunar",
+        title: "Laguna Racer",
+        port_layout: PortLayout::Unknown { orientation: 90 },
+        rom_set: Some(&LAGUNA_RACER_ROM_SET),
+    },
+];
+
+// From MAME. Laguna Racer has no machine of its own yet, so its ROM set is kept here.
+const LAGUNA_RACER_ROM_SET: RomSet = RomSet {
+    name: "lagunar",
+    files: &[
+        RomFile {
+            file_name: "lagunar.h",
+            load_address: 0x0000,
+            size: 0x0800,
+            crc32: 0x0CD5_A280,
+            sha1: "89a744c912070f11b0b90b0cc92061e238b00b64",
+        },
+        RomFile {
+            file_name: "lagunar.g",
+            load_address: 0x0800,
+            size: 0x0800,
+            crc32: 0x824C_D6F5,
+            sha1: "a74f6983787cf040eab6f19de2669c019962b9cb",
+        },
+        RomFile {
+            file_name: "lagunar.f",
+            load_address: 0x1000,
+            size: 0x0800,
+            crc32: 0x6269_2CA7,
+            sha1: "d62051bd1b45ca6e60df83942ff26a64ae25a97b",
+        },
+        RomFile {
+            file_name: "lagunar.e",
+            load_address: 0x1800,
+            size: 0x0800,
+            crc32: 0x20E0_98ED,
+            sha1: "e0c52c013f5e93794b363d7762ce0f34ba98c660",
+        },
+    ],
+};
+
+pub fn find_by_name(name: &str) -> Option<&'static Game> {
+    KNOWN_GAMES
+        .iter()
+        .find(|game| game.name.eq_ignore_ascii_case(name))
+}
+
+// Matches a combined ROM image by the CRC-32 of each ROM file within it, at its load address
+pub fn identify_rom_image(rom_image: &[u8]) -> Option<&'static Game> {
+    identify_rom_image_among(&KNOWN_GAMES, rom_image)
+}
+
+// Matches a ROM set's files by their CRC-32s, whatever they are named
+pub fn identify_rom_files(files: &HashMap<String, Vec<u8>>) -> Option<&'static Game> {
+    identify_rom_files_among(&KNOWN_GAMES, files)
+}
+
+fn identify_rom_image_among<'a>(games: &'a [Game], rom_image: &[u8]) -> Option<&'a Game> {
+    games.iter().find(|game| {
+        game.rom_set.is_some_and(|rom_set| {
+            rom_set.files.iter().all(|rom_file| {
+                let start = usize::from(rom_file.load_address);
+                rom_image
+                    .get(start..start + rom_file.size)
+                    .is_some_and(|bytes| checksum::crc32(bytes) == rom_file.crc32)
+            })
+        })
+    })
+}
+
+fn identify_rom_files_among<'a>(
+    games: &'a [Game],
+    files: &HashMap<String, Vec<u8>>,
+) -> Option<&'a Game> {
+    games.iter().find(|game| {
+        game.rom_set.is_some_and(|rom_set| {
+            rom_set.files.iter().all(|rom_file| {
+                files
+                    .values()
+                    .any(|contents| checksum::crc32(contents) == rom_file.crc32)
+            })
+        })
+    })
+}
+
+impl Game {
+    pub fn create_machine(&self) -> Box<dyn Machine> {
+        match self.port_layout {
+            PortLayout::SpaceInvaders => Box::<SpaceInvadersMachine>::default(),
+            PortLayout::BootHill => Box::<BootHillMachine>::default(),
+            PortLayout::Unknown { orientation } => Box::new(
+                BlankMachine::from_name_and_orientation(self.title.to_owned(), orientation),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The CRC-32s of [0x00; 4] and [0x01; 4]
+    const TEST_GAMES: [Game; 1] = [Game {
+        name: "test",
+        title: "Test",
+        port_layout: PortLayout::Unknown { orientation: 0 },
+        rom_set: Some(&RomSet {
+            name: "test",
+            files: &[
+                RomFile {
+                    file_name: "test.h",
+                    load_address: 0x0000,
+                    size: 4,
+                    crc32: 0x2144_DF1C,
+                    sha1: "",
+                },
+                RomFile {
+                    file_name: "test.g",
+                    load_address: 0x0004,
+                    size: 4,
+                    crc32: 0xF626_D399,
+                    sha1: "",
+                },
+            ],
+        }),
+    }];
+
+    #[test]
+    fn find_by_name_ignores_case() {
+        assert_eq!(find_by_name("BootHill").unwrap().title, "Boot Hill");
+        assert!(find_by_name("pacman").is_none());
+    }
+
+    // Zeroes followed by the four bytes that give the wanted CRC-32, so the known games' ROM sets
+    // can be checked without their ROMs
+    fn bytes_with_crc32(size: usize, crc32: u32) -> Vec<u8> {
+        let table_entry = |index: u32| {
+            (0..8).fold(index, |crc, _| {
+                if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                }
+            })
+        };
+
+        // Every table entry has a different top byte, so the entries used for the last four bytes
+        // can be found by working backwards from the wanted CRC
+        let mut indices = [0; 4];
+        let mut crc = !crc32;
+        for index in indices.iter_mut().rev() {
+            *index = (0..256)
+                .find(|index| table_entry(*index) >> 24 == crc >> 24)
+                .unwrap();
+            crc = (crc ^ table_entry(*index)) << 8;
+        }
+
+        let mut bytes = vec![0x00; size - indices.len()];
+        let mut crc = !checksum::crc32(&bytes);
+        for index in indices {
+            bytes.push((index ^ crc) as u8);
+            crc = (crc >> 8) ^ table_entry(index);
+        }
+        bytes
+    }
+
+    #[test]
+    fn known_games_create_machines_matching_their_table_entry() {
+        for game in &KNOWN_GAMES {
+            assert_eq!(game.create_machine().name(), game.title);
+        }
+        let laguna_racer = find_by_name("lagunar").unwrap().create_machine();
+        assert_eq!(laguna_racer.orientation(), 90);
+    }
+
+    #[test]
+    fn known_games_are_identified_by_their_rom_checksums() {
+        for game in &KNOWN_GAMES {
+            let files: HashMap<String, Vec<u8>> = game
+                .rom_set
+                .unwrap()
+                .files
+                .iter()
+                .map(|rom_file| {
+                    let contents = bytes_with_crc32(rom_file.size, rom_file.crc32);
+                    assert_eq!(checksum::crc32(&contents), rom_file.crc32);
+                    (rom_file.file_name.to_owned(), contents)
+                })
+                .collect();
+            let rom_image: Vec<u8> = game
+                .rom_set
+                .unwrap()
+                .files
+                .iter()
+                .flat_map(|rom_file| files[rom_file.file_name].clone())
+                .collect();
+
+            assert_eq!(
+                identify_rom_image(&rom_image).map(|game| game.name),
+                Some(game.name)
+            );
+            assert_eq!(
+                identify_rom_files(&files).map(|game| game.name),
+                Some(game.name)
+            );
+        }
+    }
+
+    #[test]
+    fn known_games_have_rom_sets_that_fill_memory_from_address_0() {
+        for game in &KNOWN_GAMES {
+            let rom_set = game.rom_set.unwrap();
+            let mut next_address = 0;
+            for rom_file in rom_set.files {
+                assert_eq!(usize::from(rom_file.load_address), next_address);
+                assert_eq!(rom_file.sha1.len(), 40);
+                next_address += rom_file.size;
+            }
+        }
+    }
+
+    #[test]
+    fn roms_are_identified_by_their_checksums() {
+        let rom_image = [[0x00; 4], [0x01; 4]].concat();
+        assert_eq!(
+            identify_rom_image_among(&TEST_GAMES, &rom_image).map(|game| game.name),
+            Some("test")
+        );
+        assert!(identify_rom_image_among(&TEST_GAMES, &rom_image[..7]).is_none());
+
+        let files = HashMap::from([
+            ("renamed.1".to_owned(), vec![0x01; 4]),
+            ("renamed.2".to_owned(), vec![0x00; 4]),
+        ]);
+        assert_eq!(
+            identify_rom_files_among(&TEST_GAMES, &files).map(|game| game.name),
+            Some("test")
+        );
+    }
+
+    #[test]
+    fn unknown_roms_are_not_identified() {
+        assert!(identify_rom_image(&[0x00; 0x2000]).is_none());
+        assert!(identify_rom_image(&[]).is_none());
+        assert!(
+            identify_rom_files(&HashMap::from([(
+                "invaders.h".to_owned(),
+                vec![0x00; 0x800]
+            )]))
+            .is_none()
+        );
+    }
+}
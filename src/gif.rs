@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+
+const MAX_COLORS: usize = 256;
+const MAX_CODE_SIZE: u8 = 12;
+const MAX_SUB_BLOCK_SIZE: usize = 255;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum GifError {
+    TooManyColors,
+    WrongFrameSize { expected: usize, found: usize },
+}
+
+impl std::fmt::Display for GifError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GifError::TooManyColors => {
+                write!(f, "A GIF frame can only have up to {MAX_COLORS} colours")
+            }
+            GifError::WrongFrameSize { expected, found } => write!(
+                f,
+                "Expected {expected} bytes of RGB pixels for a GIF frame, found {found}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GifError {}
+
+// Writes codes of a varying number of bits, starting from the lowest bit of each byte
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit_buffer: u32,
+    num_bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, code_size: u8) {
+        self.bit_buffer |= u32::from(code) << self.num_bits;
+        self.num_bits += code_size;
+        while self.num_bits >= 8 {
+            self.bytes.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.num_bits -= 8;
+        }
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        if self.num_bits > 0 {
+            self.bytes.push(self.bit_buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+// Variable-length LZW as GIF uses it. The code table starts again once it is full.
+fn lzw_compress(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code = 1_u16 << min_code_size;
+    let end_code = clear_code + 1;
+    let mut writer = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end_code + 1;
+
+    writer.write(clear_code, code_size);
+    let Some((first_index, rest)) = indices.split_first() else {
+        writer.write(end_code, code_size);
+        return writer.into_bytes();
+    };
+
+    let mut prefix = u16::from(*first_index);
+    for index in rest {
+        if let Some(code) = table.get(&(prefix, *index)) {
+            prefix = *code;
+            continue;
+        }
+
+        writer.write(prefix, code_size);
+        if next_code < 1 << MAX_CODE_SIZE {
+            table.insert((prefix, *index), next_code);
+            // The decoder adds its codes one step later, so it reads the next code at the new size
+            if next_code == 1 << code_size && code_size < MAX_CODE_SIZE {
+                code_size += 1;
+            }
+            next_code += 1;
+        } else {
+            writer.write(clear_code, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next_code = end_code + 1;
+        }
+        prefix = u16::from(*index);
+    }
+
+    writer.write(prefix, code_size);
+    writer.write(end_code, code_size);
+    writer.into_bytes()
+}
+
+// Builds an animated GIF that loops forever. Each frame has its own colour table, so the colours
+// don't need to be known up front.
+pub struct GifEncoder {
+    bytes: Vec<u8>,
+    width: u16,
+    height: u16,
+}
+
+impl GifEncoder {
+    pub fn new(width: u16, height: u16) -> Self {
+        let mut bytes = b"GIF89a".to_vec();
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        // No global colour table, background colour 0 and square pixels
+        bytes.extend_from_slice(&[0x00, 0x00, 0x00]);
+        // The Netscape extension, with a loop count of 0 for looping forever
+        bytes.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+        GifEncoder {
+            bytes,
+            width,
+            height,
+        }
+    }
+
+    // Takes RGB24 pixels row by row, as from the emulator's framebuffer
+    pub fn add_frame(
+        &mut self,
+        rgb_pixels: &[u8],
+        delay_centiseconds: u16,
+    ) -> Result<(), GifError> {
+        let expected_size = usize::from(self.width) * usize::from(self.height) * 3;
+        if rgb_pixels.len() != expected_size {
+            return Err(GifError::WrongFrameSize {
+                expected: expected_size,
+                found: rgb_pixels.len(),
+            });
+        }
+
+        let mut palette: Vec<[u8; 3]> = Vec::new();
+        let mut palette_indices: HashMap<[u8; 3], u8> = HashMap::new();
+        let mut indices = Vec::with_capacity(rgb_pixels.len() / 3);
+        for pixel in rgb_pixels.chunks_exact(3) {
+            let color: [u8; 3] = pixel.try_into().unwrap();
+            let index = match palette_indices.get(&color) {
+                Some(index) => *index,
+                None if palette.len() == MAX_COLORS => return Err(GifError::TooManyColors),
+                None => {
+                    let index = palette.len() as u8;
+                    palette.push(color);
+                    palette_indices.insert(color, index);
+                    index
+                }
+            };
+            indices.push(index);
+        }
+
+        // Colour tables have a power of two entries, and LZW codes start at 2 bits at least
+        let table_size_bits = (palette.len().max(2).next_power_of_two().trailing_zeros()) as u8;
+        palette.resize(1 << table_size_bits, [0x00; 3]);
+        let min_code_size = table_size_bits.max(2);
+
+        // The graphic control extension, which holds the delay before the next frame
+        self.bytes.extend_from_slice(&[0x21, 0xF9, 0x04, 0x00]);
+        self.bytes
+            .extend_from_slice(&delay_centiseconds.to_le_bytes());
+        self.bytes.extend_from_slice(&[0x00, 0x00]);
+
+        self.bytes.push(0x2C);
+        self.bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        self.bytes.extend_from_slice(&self.width.to_le_bytes());
+        self.bytes.extend_from_slice(&self.height.to_le_bytes());
+        self.bytes.push(0x80 | (table_size_bits - 1));
+        self.bytes.extend(palette.iter().flatten());
+
+        self.bytes.push(min_code_size);
+        for sub_block in lzw_compress(&indices, min_code_size).chunks(MAX_SUB_BLOCK_SIZE) {
+            self.bytes.push(sub_block.len() as u8);
+            self.bytes.extend_from_slice(sub_block);
+        }
+        self.bytes.push(0x00);
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.bytes.push(0x3B);
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lzw_decompress(bytes: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear_code = 1_u16 << min_code_size;
+        let end_code = clear_code + 1;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut output = Vec::new();
+        let mut bit_position = 0;
+
+        loop {
+            let mut code = 0_u16;
+            for bit in 0..code_size {
+                let byte = bytes[(bit_position + usize::from(bit)) / 8];
+                let bit_value = (byte >> ((bit_position + usize::from(bit)) % 8)) & 1;
+                code |= u16::from(bit_value) << bit;
+            }
+            bit_position += usize::from(code_size);
+
+            if code == clear_code {
+                table = (0..clear_code).map(|index| vec![index as u8]).collect();
+                table.extend([Vec::new(), Vec::new()]);
+                code_size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end_code {
+                return output;
+            }
+
+            let entry = match (table.get(usize::from(code)), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [previous.as_slice(), &previous[..1]].concat(),
+                (None, None) => panic!("Unknown first code {code}"),
+            };
+            output.extend_from_slice(&entry);
+            if let Some(previous) = previous
+                && table.len() < 1 << MAX_CODE_SIZE
+            {
+                table.push([previous.as_slice(), &entry[..1]].concat());
+                if table.len() == 1 << code_size && code_size < MAX_CODE_SIZE {
+                    code_size += 1;
+                }
+            }
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_output_decompresses_to_the_input() {
+        let repetitive: Vec<u8> = (0..10_000).map(|index| (index / 7 % 3) as u8).collect();
+        assert_eq!(lzw_decompress(&lzw_compress(&repetitive, 2), 2), repetitive);
+
+        // Enough unique sequences to fill the code table, so it has to start again
+        let varied: Vec<u8> = (0_u32..60_000)
+            .map(|index| (index.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        assert_eq!(lzw_decompress(&lzw_compress(&varied, 8), 8), varied);
+
+        assert_eq!(lzw_decompress(&lzw_compress(&[], 2), 2), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn frames_have_a_local_colour_table_and_delay() {
+        let mut encoder = GifEncoder::new(2, 1);
+        encoder
+            .add_frame(&[0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF], 3)
+            .unwrap();
+        let bytes = encoder.finish();
+
+        assert_eq!(&bytes[..6], b"GIF89a");
+        let frame = &bytes[13 + 19..];
+        assert_eq!(frame[..8], [0x21, 0xF9, 0x04, 0x00, 0x03, 0x00, 0x00, 0x00]);
+        assert_eq!(frame[8..18], [0x2C, 0, 0, 0, 0, 2, 0, 1, 0, 0x80]);
+        assert_eq!(frame[18..24], [0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(frame[24], 2);
+        assert_eq!(bytes[bytes.len() - 1], 0x3B);
+    }
+
+    #[test]
+    fn frames_with_too_many_colours_are_rejected() {
+        let pixels: Vec<u8> = (0_u32..300)
+            .flat_map(|index| [index as u8, (index >> 8) as u8, 0])
+            .collect();
+        let mut encoder = GifEncoder::new(300, 1);
+        assert_eq!(encoder.add_frame(&pixels, 3), Err(GifError::TooManyColors));
+    }
+
+    #[test]
+    fn frames_of_the_wrong_size_are_rejected() {
+        let mut encoder = GifEncoder::new(2, 2);
+        assert_eq!(
+            encoder.add_frame(&[0x00; 9], 3),
+            Err(GifError::WrongFrameSize {
+                expected: 12,
+                found: 9
+            })
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use crate::machine::Input;
+
+pub const DEFAULT_KEY_BINDINGS: &str = include_str!("../keybindings.toml");
+const DEFAULT_SECTION: &str = "default";
+
+// Emulator hotkeys that are handled before the key bindings, so they can't be bound to an action
+pub const RESERVED_KEYS: [&str; 8] = ["Escape", "P", "F3", "F5", "F6", "F7", "F9", "F12"];
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeyBindingsError {
+    pub line_number: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for KeyBindingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: {}", self.line_number, self.message)
+    }
+}
+
+impl std::error::Error for KeyBindingsError {}
+
+// A small subset of TOML: `[section]` headers, then `action = "Key"` or `action = ["Key", "Key"]`.
+// The `default` section applies to every game, and a section named after a game's file stem
+// replaces the keys for any actions it lists.
+#[derive(Default, Debug)]
+pub struct KeyBindingsConfig {
+    sections: HashMap<String, Vec<(Input, Vec<String>)>>,
+}
+
+impl KeyBindingsConfig {
+    pub fn parse(text: &str) -> Result<Self, KeyBindingsError> {
+        let mut config = KeyBindingsConfig::default();
+        let mut section_name = DEFAULT_SECTION.to_owned();
+
+        for (line_index, line) in text.lines().enumerate() {
+            let error = |message: String| KeyBindingsError {
+                line_number: line_index + 1,
+                message,
+            };
+            let line = strip_comment(line).trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                section_name = name.trim().to_owned();
+                continue;
+            }
+
+            let (action_name, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("Expected `action = \"Key\"`, found `{line}`")))?;
+            let action_name = action_name.trim();
+            let input = Input::from_name(action_name)
+                .ok_or_else(|| error(format!("Unknown action `{action_name}`")))?;
+            let key_names = parse_key_names(value.trim()).map_err(error)?;
+
+            if let Some(key_name) = key_names.iter().find(|key_name| is_reserved_key(key_name)) {
+                return Err(error(format!(
+                    "`{key_name}` is reserved by the emulator and can't be bound to `{action_name}`"
+                )));
+            }
+
+            config
+                .sections
+                .entry(section_name.clone())
+                .or_default()
+                .push((input, key_names));
+        }
+
+        Ok(config)
+    }
+
+    pub fn bindings_for_game(&self, game_name: &str) -> KeyBindings {
+        let mut input_keys: HashMap<Input, Vec<String>> = HashMap::new();
+
+        for section_name in [DEFAULT_SECTION, game_name] {
+            for (input, key_names) in self.sections.get(section_name).into_iter().flatten() {
+                input_keys.insert(*input, key_names.clone());
+            }
+        }
+
+        KeyBindings { input_keys }
+    }
+}
+
+fn is_reserved_key(key_name: &str) -> bool {
+    RESERVED_KEYS
+        .iter()
+        .any(|reserved_key| reserved_key.eq_ignore_ascii_case(key_name))
+}
+
+// A `#` inside quotes is a key name rather than the start of a comment
+fn strip_comment(line: &str) -> &str {
+    let mut is_in_quotes = false;
+
+    for (index, character) in line.char_indices() {
+        match character {
+            '"' => is_in_quotes = !is_in_quotes,
+            '#' if !is_in_quotes => return &line[..index],
+            _ => {}
+        }
+    }
+
+    line
+}
+
+fn parse_key_names(value: &str) -> Result<Vec<String>, String> {
+    let quoted_names = match value.strip_prefix('[') {
+        Some(rest) => rest
+            .strip_suffix(']')
+            .ok_or_else(|| format!("Unterminated list `{value}`"))?
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect(),
+        None => vec![value],
+    };
+
+    quoted_names
+        .into_iter()
+        .map(|quoted_name| {
+            quoted_name
+                .strip_prefix('"')
+                .and_then(|rest| rest.strip_suffix('"'))
+                .map(str::to_owned)
+                .ok_or_else(|| format!("Expected a quoted key name, found `{quoted_name}`"))
+        })
+        .collect()
+}
+
+#[derive(Default, Debug)]
+pub struct KeyBindings {
+    input_keys: HashMap<Input, Vec<String>>,
+}
+
+impl KeyBindings {
+    pub fn keys_for_input(&self, input: Input) -> &[String] {
+        self.input_keys.get(&input).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn input_for_key(&self, key_name: &str) -> Option<Input> {
+        Input::all().find(|input| {
+            self.keys_for_input(*input)
+                .iter()
+                .any(|bound_key_name| bound_key_name.eq_ignore_ascii_case(key_name))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_sections_override_the_default_section() {
+        let config = KeyBindingsConfig::parse(
+            "
+            # Shared keys
+            [default]
+            coin = \"Right Shift\"
+            p1_fire = [\"Space\", \"Left Ctrl\"] # Either key
+            tilt = \"#\"
+
+            [invaders]
+            p1_fire = \"Up\"",
+        )
+        .unwrap();
+
+        let key_bindings = config.bindings_for_game("invaders");
+        assert_eq!(key_bindings.input_for_key("right shift"), Some(Input::Coin));
+        assert_eq!(key_bindings.input_for_key("Up"), Some(Input::P1Fire));
+        assert_eq!(key_bindings.input_for_key("Space"), None);
+        assert_eq!(key_bindings.input_for_key("#"), Some(Input::Tilt));
+
+        let key_bindings = config.bindings_for_game("boothill");
+        assert_eq!(key_bindings.input_for_key("Left Ctrl"), Some(Input::P1Fire));
+        assert_eq!(
+            key_bindings.keys_for_input(Input::P1Fire),
+            ["Space", "Left Ctrl"]
+        );
+    }
+
+    #[test]
+    fn parse_reports_errors_with_line_numbers() {
+        assert_eq!(
+            KeyBindingsConfig::parse("coin = \"Return\"\nfly = \"Up\"").unwrap_err(),
+            KeyBindingsError {
+                line_number: 2,
+                message: "Unknown action `fly`".to_owned(),
+            }
+        );
+        assert_eq!(
+            KeyBindingsConfig::parse("coin = Return").unwrap_err(),
+            KeyBindingsError {
+                line_number: 1,
+                message: "Expected a quoted key name, found `Return`".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_rejects_reserved_keys() {
+        assert_eq!(
+            KeyBindingsConfig::parse(
+                "coin = \"Return\"\n[invaders]\np1_fire = [\"Space\", \"f5\"]"
+            )
+            .unwrap_err(),
+            KeyBindingsError {
+                line_number: 3,
+                message: "`f5` is reserved by the emulator and can't be bound to `p1_fire`"
+                    .to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn default_key_bindings_bind_every_action() {
+        let key_bindings = KeyBindingsConfig::parse(DEFAULT_KEY_BINDINGS)
+            .unwrap()
+            .bindings_for_game("invaders");
+
+        for input in Input::all() {
+            assert!(!key_bindings.keys_for_input(input).is_empty(), "{input:?}");
+        }
+    }
+}
@@ -2,19 +2,47 @@ use std::collections::HashMap;
 
 use enum_map::{Enum, EnumMap};
 use log::{Level, debug, log_enabled};
+
+use crate::machine::{AudioEvent, TimedAudioEvent};
+use crate::memory::FlatMemory;
+use crate::save_state::{SaveStateError, SaveStateReader, SaveStateWriter};
+use crate::watchpoints::{Access, BreakEvent, PortDirection, Watchpoints};
 // #[cfg(test)]
 // use mutagen::mutate;
 
 pub mod arithmetic_instructions;
+pub mod assembler;
+pub mod audio_recorder;
 #[cfg(test)]
 pub mod base_test_functions;
 pub mod bit_operations;
+pub mod boot_hill;
 pub mod branch_instructions;
+pub mod checksum;
+pub mod code_tracer;
+pub mod debugger;
 pub mod disassembler;
+pub mod emulator;
+pub mod games;
+pub mod gif;
+pub mod key_bindings;
 pub mod logical_instructions;
+pub mod machine;
+pub mod memory;
+pub mod options;
+pub mod png;
+pub mod rewind;
+pub mod rom_set;
 pub mod runner;
+pub mod save_state;
+pub mod scheduler;
+pub mod space_invaders;
 pub mod stack_instructions;
+pub mod synth;
 pub mod transfer_instructions;
+pub mod watchpoints;
+pub mod wav;
+pub mod zip_archive;
 
 #[derive(Copy, Clone, Enum, Eq, PartialEq, Hash, Debug)]
 pub enum Register {
@@ -64,26 +92,137 @@ pub enum ConditionFlag {
 pub type ConditionFlags = EnumMap<ConditionFlag, bool>;
 pub type Condition = (ConditionFlag, bool);
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CpuError {
+    UnsupportedRegisterPair(RegisterPair, &'static str),
+    UnsupportedCondition(ConditionFlag, &'static str),
+    InvalidResetIndex(u8),
+    InvalidBitIndex(u8),
+    InvalidInputPort(u8),
+    InvalidOutputPort(u8),
+}
+
+impl std::fmt::Display for CpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CpuError::UnsupportedRegisterPair(register_pair, base_instruction) => write!(
+                f,
+                "The register pair {register_pair} is not supported by the {base_instruction} operation"
+            ),
+            CpuError::UnsupportedCondition(ConditionFlag::AuxiliaryCarry, base_instruction) => {
+                write!(
+                    f,
+                    "The auxiliary carry flag is not a supported condition for {base_instruction}"
+                )
+            }
+            CpuError::UnsupportedCondition(condition_flag, base_instruction) => write!(
+                f,
+                "The {condition_flag:?} flag is not a supported condition for {base_instruction}"
+            ),
+            CpuError::InvalidResetIndex(reset_index) => {
+                write!(f, "Invalid reset index of {reset_index}")
+            }
+            CpuError::InvalidBitIndex(bit_index) => write!(f, "Invalid bit index of {bit_index}"),
+            CpuError::InvalidInputPort(port_number) => {
+                write!(f, "Invalid input Port {port_number}")
+            }
+            CpuError::InvalidOutputPort(port_number) => {
+                write!(f, "Invalid output Port {port_number}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct CpuFault {
+    pub program_counter: u16,
+    pub op_code: u8,
+    pub error: CpuError,
+}
+
+impl std::fmt::Display for CpuFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (PC: {:04X}, opcode: {:02X})",
+            self.error, self.program_counter, self.op_code
+        )
+    }
+}
+
+impl std::error::Error for CpuFault {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+// Why running stopped early. After a break the instruction has finished, so running can carry on.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CpuStop {
+    Fault(CpuFault),
+    Break(BreakEvent),
+}
+
+impl std::fmt::Display for CpuStop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CpuStop::Fault(fault) => write!(f, "CPU fault: {fault}"),
+            CpuStop::Break(event) => write!(f, "Hit {}: {event}", event.breakpoint_kind()),
+        }
+    }
+}
+
+impl std::error::Error for CpuStop {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CpuStop::Fault(fault) => Some(fault),
+            CpuStop::Break(_) => None,
+        }
+    }
+}
+
 pub trait Ports {
-    fn read_in_port(&self, port_number: u8) -> u8;
-    fn write_out_port(&mut self, port_number: u8, value: u8);
+    fn read_in_port(&self, port_number: u8) -> Result<u8, CpuError>;
+    fn write_out_port(&mut self, port_number: u8, value: u8) -> Result<(), CpuError>;
     fn in_port_static_value(&self, port_number: u8) -> Option<u8>;
     fn set_in_port_static_value(&mut self, port_number: u8, value: u8);
+    // Ports with internal state, such as shift registers and latched outputs, should include it in save states
+    fn save_state(&self, _writer: &mut SaveStateWriter) {}
+    fn load_state(&mut self, _reader: &mut SaveStateReader) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+    // Ports that drive sound hardware report what was triggered since the last call
+    fn take_audio_events(&mut self) -> Vec<AudioEvent> {
+        Vec::new()
+    }
 }
 
 struct DefaultPorts;
 
 impl Ports for DefaultPorts {
-    fn read_in_port(&self, _port_number: u8) -> u8 {
-        0
+    fn read_in_port(&self, _port_number: u8) -> Result<u8, CpuError> {
+        Ok(0)
+    }
+    fn write_out_port(&mut self, _port_number: u8, _value: u8) -> Result<(), CpuError> {
+        Ok(())
     }
-    fn write_out_port(&mut self, _port_number: u8, _value: u8) {}
     fn in_port_static_value(&self, _port_number: u8) -> Option<u8> {
         None
     }
     fn set_in_port_static_value(&mut self, _port_number: u8, _value: u8) {}
 }
 
+pub trait Memory {
+    fn read(&self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    // Writes even where the CPU can't, such as when loading a ROM or editing memory in the debugger
+    fn poke(&mut self, address: u16, value: u8) {
+        self.write(address, value);
+    }
+}
+
 const MEMORY_SIZE: usize = u16::MAX as usize + 1;
 
 const CONDITION_FLAG_BITS: [(ConditionFlag, u8); 5] = [
@@ -99,11 +238,14 @@ pub struct State {
     pub condition_flags: ConditionFlags,
     pub program_counter: u16,
     pub stack_pointer: u16,
-    pub memory: [u8; MEMORY_SIZE],
+    pub memory: Box<dyn Memory>,
     pub are_interrupts_enabled: bool,
     pub is_halted: bool,
     pub ports: Box<dyn Ports>,
+    pub watchpoints: Watchpoints,
     cpu_total_state_count: usize,
+    operation_program_counter: u16,
+    audio_events: Vec<TimedAudioEvent>,
 }
 
 impl Default for State {
@@ -115,14 +257,72 @@ impl Default for State {
 
 impl State {
     pub fn load_memory(&mut self, contiguous_memory_bytes: &[u8]) {
-        for (memory_address, memory_value) in contiguous_memory_bytes.iter().enumerate() {
-            self.memory[memory_address] = *memory_value;
+        self.load_memory_at(0x0000, contiguous_memory_bytes);
+    }
+
+    pub fn load_memory_at(&mut self, start_address: u16, contiguous_memory_bytes: &[u8]) {
+        for (offset, memory_value) in contiguous_memory_bytes.iter().enumerate() {
+            self.memory
+                .poke(start_address.wrapping_add(offset as u16), *memory_value);
         }
     }
 
     // #[cfg_attr(test, mutate)]
     pub fn memory_value_at_pc(&self) -> u8 {
-        self.memory[self.program_counter as usize]
+        self.memory.read(self.program_counter)
+    }
+
+    // Memory accessed by instructions goes through here so that watchpoints can see it
+    pub fn read_memory(&mut self, address: u16) -> u8 {
+        let value = self.memory.read(address);
+        if !self.watchpoints.is_empty() {
+            self.watchpoints.check_memory_access(
+                self.operation_program_counter,
+                Access::Read,
+                address,
+                value,
+            );
+        }
+        value
+    }
+
+    pub fn write_memory(&mut self, address: u16, value: u8) {
+        self.memory.write(address, value);
+        if !self.watchpoints.is_empty() {
+            self.watchpoints.check_memory_access(
+                self.operation_program_counter,
+                Access::Write,
+                address,
+                value,
+            );
+        }
+    }
+
+    pub fn read_in_port(&mut self, port_number: u8) -> Result<u8, CpuError> {
+        let value = self.ports.read_in_port(port_number)?;
+        if !self.watchpoints.is_empty() {
+            self.watchpoints.check_port_access(
+                self.operation_program_counter,
+                PortDirection::In,
+                port_number,
+                value,
+            );
+        }
+        Ok(value)
+    }
+
+    pub fn write_out_port(&mut self, port_number: u8, value: u8) -> Result<(), CpuError> {
+        self.ports.write_out_port(port_number, value)?;
+        self.collect_port_audio_events();
+        if !self.watchpoints.is_empty() {
+            self.watchpoints.check_port_access(
+                self.operation_program_counter,
+                PortDirection::Out,
+                port_number,
+                value,
+            );
+        }
+        Ok(())
     }
 
     // #[cfg_attr(test, mutate)]
@@ -194,7 +394,12 @@ impl State {
 
     // #[cfg_attr(test, mutate)]
     pub fn set_condition_flag_byte(&mut self, memory_address: u16) {
-        let condition_flag_byte = self.memory[memory_address as usize];
+        let condition_flag_byte = self.read_memory(memory_address);
+        self.set_condition_flags_from_byte(condition_flag_byte);
+    }
+
+    // #[cfg_attr(test, mutate)]
+    pub fn set_condition_flags_from_byte(&mut self, condition_flag_byte: u8) {
         for (condition_flag, bit_index) in &CONDITION_FLAG_BITS {
             self.condition_flags[*condition_flag] =
                 bit_operations::is_bit_set(condition_flag_byte, *bit_index);
@@ -271,9 +476,37 @@ impl State {
         self.cpu_total_state_count
     }
 
+    // Sounds triggered by OUT instructions since the last call
+    fn collect_port_audio_events(&mut self) {
+        for event in self.ports.take_audio_events() {
+            self.audio_events.push(TimedAudioEvent {
+                state_count: self.cpu_total_state_count,
+                event,
+            });
+        }
+    }
+
+    pub fn take_audio_events(&mut self) -> Vec<TimedAudioEvent> {
+        std::mem::take(&mut self.audio_events)
+    }
+
+    // For time passing while the CPU is halted
+    pub fn skip_to_state_count(&mut self, state_count: usize) {
+        self.cpu_total_state_count = self.cpu_total_state_count.max(state_count);
+    }
+
     pub fn run_operation(&mut self, operation: &Operation) {
+        if let Err(fault) = self.try_run_operation(operation) {
+            panic!("{fault}");
+        }
+    }
+
+    // On a fault the program counter is left pointing at the faulting instruction,
+    // so the host can inspect the state and decide whether to skip it or stop.
+    pub fn try_run_operation(&mut self, operation: &Operation) -> Result<(), CpuFault> {
         let op_code_pc = self.program_counter;
-        self.program_counter += 1;
+        self.operation_program_counter = op_code_pc;
+        self.program_counter = self.program_counter.wrapping_add(1);
 
         let mut additional_byte_1 = None;
         let mut additional_byte_2 = None;
@@ -283,17 +516,29 @@ impl State {
             || instruction_data_type == InstructionDataType::LowHigh
         {
             additional_byte_1 = Some(self.memory_value_at_pc());
-            self.program_counter += 1;
+            self.program_counter = self.program_counter.wrapping_add(1);
         }
 
         if instruction_data_type == InstructionDataType::LowHigh {
             additional_byte_2 = Some(self.memory_value_at_pc());
-            self.program_counter += 1;
+            self.program_counter = self.program_counter.wrapping_add(1);
         }
 
         self.log_current_state(op_code_pc);
-        runner::run_operation(operation, self, additional_byte_1, additional_byte_2);
+
+        if let Err(error) =
+            runner::try_run_operation(operation, self, additional_byte_1, additional_byte_2)
+        {
+            self.program_counter = op_code_pc;
+            return Err(CpuFault {
+                program_counter: op_code_pc,
+                op_code: self.memory.read(op_code_pc),
+                error,
+            });
+        }
+
         self.cpu_total_state_count += operation.machine_states(self) as usize;
+        Ok(())
     }
 
     fn log_current_state(&self, op_code_pc: u16) {
@@ -311,10 +556,10 @@ impl State {
                 self.full_rp_value(RegisterPair::HL),
                 self.stack_pointer,
                 self.cpu_total_state_count,
-                self.memory[op_code_pc as usize],
-                self.memory[op_code_pc as usize + 1],
-                self.memory[op_code_pc as usize + 2],
-                self.memory[op_code_pc as usize + 3],
+                self.memory.read(op_code_pc),
+                self.memory.read(op_code_pc.wrapping_add(1)),
+                self.memory.read(op_code_pc.wrapping_add(2)),
+                self.memory.read(op_code_pc.wrapping_add(3)),
             );
         }
     }
@@ -388,7 +633,7 @@ impl StateBuilder {
     pub fn build(&self) -> State {
         let mut registers = RegisterState::default();
         let mut condition_flags = ConditionFlags::default();
-        let mut memory = [0; MEMORY_SIZE];
+        let mut memory = FlatMemory::default();
 
         if let Some(rvs) = &self.register_values {
             for (register, value) in rvs {
@@ -404,7 +649,7 @@ impl StateBuilder {
 
         if let Some(mvs) = &self.memory_values {
             for (memory_address, value) in mvs {
-                memory[*memory_address as usize] = *value;
+                memory.poke(*memory_address, *value);
             }
         }
 
@@ -413,23 +658,26 @@ impl StateBuilder {
             condition_flags,
             program_counter: self.program_counter.unwrap_or(0x0000),
             stack_pointer: self.stack_pointer.unwrap_or(0x0000),
-            memory,
+            memory: Box::new(memory),
             are_interrupts_enabled: self.are_interrupts_enabled.unwrap_or(false),
             is_halted: self.is_halted.unwrap_or(false),
             ports: Box::new(DefaultPorts),
+            watchpoints: Watchpoints::default(),
             cpu_total_state_count: 0,
+            operation_program_counter: 0x0000,
+            audio_events: Vec::new(),
         }
     }
 }
 
-#[derive(Eq, PartialEq)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum InstructionDataType {
     None,
     Single,
     LowHigh,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Operation {
     Mov(Register, Register),
     MovFromMem(Register),
@@ -656,6 +904,7 @@ impl Operation {
 mod tests {
     use super::*;
     use base_test_functions::assert_state_is_as_expected;
+    use maplit::hashmap;
 
     #[test]
     fn default_state_has_all_default_values() {
@@ -670,4 +919,80 @@ mod tests {
         assert_eq!((0x0F, 0xF0), state.low_high_rp_value(RegisterPair::SP));
         assert_eq!(state.stack_pointer, state.full_rp_value(RegisterPair::SP));
     }
+
+    struct NoPorts;
+
+    impl Ports for NoPorts {
+        fn read_in_port(&self, port_number: u8) -> Result<u8, CpuError> {
+            Err(CpuError::InvalidInputPort(port_number))
+        }
+        fn write_out_port(&mut self, port_number: u8, _value: u8) -> Result<(), CpuError> {
+            Err(CpuError::InvalidOutputPort(port_number))
+        }
+        fn in_port_static_value(&self, _port_number: u8) -> Option<u8> {
+            None
+        }
+        fn set_in_port_static_value(&mut self, _port_number: u8, _value: u8) {}
+    }
+
+    #[test]
+    fn try_run_operation_reports_unsupported_operations_as_a_fault() {
+        let mut state = StateBuilder::default()
+            .program_counter(0x1234)
+            .stack_pointer(0x2400)
+            .memory_values(hashmap! { 0x1234 => 0xF5 })
+            .build();
+        let result = state.try_run_operation(&Operation::Push(RegisterPair::SP));
+        assert_eq!(
+            result,
+            Err(CpuFault {
+                program_counter: 0x1234,
+                op_code: 0xF5,
+                error: CpuError::UnsupportedRegisterPair(RegisterPair::SP, "PUSH"),
+            })
+        );
+        assert_state_is_as_expected(
+            &state,
+            &StateBuilder::default()
+                .program_counter(0x1234)
+                .stack_pointer(0x2400)
+                .memory_values(hashmap! { 0x1234 => 0xF5 })
+                .build(),
+        );
+        assert_eq!(state.cpu_total_state_count(), 0);
+    }
+
+    #[test]
+    fn try_run_operation_reports_invalid_ports_as_a_fault() {
+        let mut state = StateBuilder::default()
+            .register_values(hashmap! { Register::A => 0x42 })
+            .memory_values(hashmap! { 0x0000 => 0xDB, 0x0001 => 0x07 })
+            .build();
+        state.ports = Box::new(NoPorts);
+        let fault = state.try_run_operation(&Operation::In).unwrap_err();
+        assert_eq!(fault.error, CpuError::InvalidInputPort(0x07));
+        assert_eq!(
+            fault.to_string(),
+            "Invalid input Port 7 (PC: 0000, opcode: DB)"
+        );
+        assert_eq!(state.program_counter, 0x0000);
+        assert_eq!(state.registers[Register::A], 0x42);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid output Port 16 (PC: 0000, opcode: D3)")]
+    fn run_operation_panics_on_a_fault() {
+        let mut state = StateBuilder::default()
+            .memory_values(hashmap! { 0x0000 => 0xD3, 0x0001 => 0x10 })
+            .build();
+        state.ports = Box::new(NoPorts);
+        state.run_operation(&Operation::Out);
+    }
+
+    #[test]
+    fn run_operation_wraps_the_program_counter_at_the_end_of_memory() {
+        let mut state = StateBuilder::default().program_counter(0xFFFF).build();
+        state.run_operation(&Operation::Nop);
+        assert_eq!(state.program_counter, 0x0000);
+    }
 }
@@ -11,7 +11,7 @@ pub fn ana_instruction(state: &mut State, source_register: Register) {
 // #[cfg_attr(test, mutate)]
 pub fn ana_mem_instruction(state: &mut State) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    let memory_value = state.memory[memory_address as usize];
+    let memory_value = state.read_memory(memory_address);
     ani_instruction(state, memory_value);
 }
 
@@ -39,7 +39,7 @@ pub fn xra_instruction(state: &mut State, source_register: Register) {
 // #[cfg_attr(test, mutate)]
 pub fn xra_mem_instruction(state: &mut State) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    let memory_value = state.memory[memory_address as usize];
+    let memory_value = state.read_memory(memory_address);
     xri_instruction(state, memory_value);
 }
 
@@ -62,7 +62,7 @@ pub fn ora_instruction(state: &mut State, source_register: Register) {
 // #[cfg_attr(test, mutate)]
 pub fn ora_mem_instruction(state: &mut State) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    let memory_value = state.memory[memory_address as usize];
+    let memory_value = state.read_memory(memory_address);
     ori_instruction(state, memory_value);
 }
 
@@ -85,7 +85,7 @@ pub fn cmp_instruction(state: &mut State, register: Register) {
 // #[cfg_attr(test, mutate)]
 pub fn cmp_mem_instruction(state: &mut State) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    let memory_value = state.memory[memory_address as usize];
+    let memory_value = state.read_memory(memory_address);
     cpi_instruction(state, memory_value);
 }
 
@@ -1,79 +1,173 @@
 extern crate sdl2;
 
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::thread;
 use std::time::{Duration, Instant};
-use std::{env, fs};
+use std::{env, fs, io, process};
 
-use log::debug;
+use enum_map::Enum;
+use log::{error, info, warn};
 use sdl2::EventPump;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::mixer;
+use sdl2::mixer::{Channel, Chunk};
 use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::render::{Texture, WindowCanvas};
 
-use emu_8080::{State, runner};
-
-use crate::machine::Machine;
-
-mod machine;
-
-const FRAME_RATE: u64 = 60;
-const ORIGINAL_SCREEN_WIDTH: u32 = 256;
-const ORIGINAL_SCREEN_HEIGHT: u32 = 224;
-const NUM_PIXEL_COMPONENTS: usize = 3;
-const SCREEN_DATA_SIZE: usize =
-    (ORIGINAL_SCREEN_WIDTH * ORIGINAL_SCREEN_HEIGHT) as usize * NUM_PIXEL_COMPONENTS;
-
-fn main() -> Result<(), String> {
-    env_logger::init();
-
-    let args: Vec<String> = env::args().collect();
-    let file_name = args
-        .get(1)
-        .expect("Must provide a filename argument for a game to play")
-        .as_str();
-
-    // Based on audio file bitrate of 88kbps
-    mixer::open_audio(11_025, mixer::AUDIO_U8, 1, 1_024)?;
-
-    let expected_extension = ".bin";
-    let mut machine: Box<dyn Machine> = match file_name {
-        "invaders.bin" => Box::<machine::SpaceInvadersMachine>::default(),
-        "boothill.bin" => Box::<machine::BootHillMachine>::default(),
-        _ => {
-            if &file_name[file_name.len() - expected_extension.len()..file_name.len()]
-                == expected_extension
-            {
-                let machine_name = &file_name[..file_name.len() - expected_extension.len()];
-                let orientation = match machine_name {
-                    "lagunar" => 90,
-                    _ => 0,
-                };
-                Box::new(machine::BlankMachine::from_name_and_orientation(
-                    machine_name.to_string(),
-                    orientation,
-                ))
-            } else {
-                panic!("Can't play game with filename {file_name}");
-            }
+use emu_8080::audio_recorder::AudioRecorder;
+use emu_8080::emulator::{Emulator, NUM_PIXEL_COMPONENTS};
+use emu_8080::games::{self, Game, PortLayout};
+use emu_8080::gif::GifEncoder;
+use emu_8080::key_bindings::{DEFAULT_KEY_BINDINGS, KeyBindingsConfig};
+use emu_8080::machine::{AudioEvent, BlankMachine, Input, Machine, SoundKind, SoundName};
+use emu_8080::options::{self, Command, PlayOptions, USAGE};
+use emu_8080::rewind::RewindBuffer;
+use emu_8080::{
+    State, code_tracer, debugger, disassembler, png, rom_set, save_state, scheduler, synth, wav,
+};
+
+mod controllers;
+
+use crate::controllers::Controllers;
+
+const MAX_FRAMES_BEHIND: u32 = 5;
+const NUM_SAVE_STATE_SLOTS: u8 = 10;
+// Enough for 20 seconds of rewinding
+const REWIND_SNAPSHOT_INTERVAL: u64 = 2;
+const REWIND_CAPACITY: usize = 600;
+const KEY_BINDINGS_FILE_NAME: &str = "keybindings.toml";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match options::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {e}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(log_level) = options.log_level {
+        logger.filter_level(log_level);
+    }
+    logger.init();
+
+    if let Err(e) = run(options.command) {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Help => print!("{USAGE}"),
+        Command::ListMachines => print!("{}", machine_listing()),
+        Command::Disassemble(rom_file_name) => {
+            let file_bytes = read_file(&rom_file_name)?;
+            print!("{}", disassembler::disassemble_listing(&file_bytes, 0x0000));
+        }
+        Command::DisassembleTraced(rom_file_name) => {
+            let file_bytes = read_file(&rom_file_name)?;
+            let code_map =
+                code_tracer::trace(&file_bytes, 0x0000, &code_tracer::DEFAULT_ENTRY_POINTS);
+            print!("{}", code_map.listing(&file_bytes));
         }
+        Command::Debug(rom_file_name) => {
+            let file_bytes = read_file(&rom_file_name)?;
+            let mut state = State::default();
+            state.load_memory(&file_bytes);
+            debugger::run_repl(&mut state, &mut io::stdin().lock(), &mut io::stdout())
+                .map_err(|e| e.to_string())?;
+        }
+        Command::Play(play_options) => play(&play_options)?,
+    }
+    Ok(())
+}
+
+fn read_file(file_name: &str) -> Result<Vec<u8>, String> {
+    fs::read(file_name).map_err(|e| format!("Could not read a file with filename {file_name}: {e}"))
+}
+
+fn file_stem(file_name: &str) -> String {
+    Path::new(file_name)
+        .file_stem()
+        .map(|file_stem| file_stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn play(options: &PlayOptions) -> Result<(), String> {
+    let (machine, game) = load_machine(options)?;
+    if options.should_list_dip_switches {
+        print!("{}", dip_switch_listing(&*machine));
+        return Ok(());
+    }
+
+    let sound_files: &[(SoundName, &str)] = match game.map(|game| game.port_layout) {
+        Some(PortLayout::SpaceInvaders) => &SPACE_INVADERS_SOUND_FILES,
+        Some(PortLayout::BootHill) => &BOOT_HILL_SOUND_FILES,
+        _ => &[],
     };
+    let mut audio_recorder = options.audio_recording_path.as_ref().map(|_| {
+        AudioRecorder::new(
+            recording_mixer(sound_files, options.volume),
+            machine.state().cpu_total_state_count(),
+        )
+    });
+
+    if options.is_headless {
+        let mut emulator = Emulator::new(machine);
+        let mut frame_capture = FrameCapture::from_options(options, &emulator)?;
+        let result = run_headless(
+            &mut emulator,
+            options.frame_limit.unwrap_or(0),
+            audio_recorder.as_mut(),
+            &mut frame_capture,
+        );
+        save_audio_recording(options, audio_recorder.as_ref())?;
+        frame_capture.finish(options, &emulator)?;
+        return result;
+    }
+
+    // With no sound player, as the audio device is never opened when muted
+    let mut sound_player = None;
+    if !options.is_muted {
+        // Based on audio file bitrate of 88kbps
+        mixer::open_audio(synth::SAMPLE_RATE as i32, mixer::AUDIO_U8, 1, 1_024)?;
+        mixer::allocate_channels(SoundName::LENGTH as i32 + 1);
+        sound_player = Some(SoundPlayer::from_sound_files(sound_files, options.volume));
+    }
 
-    let file_bytes = fs::read(file_name)
-        .unwrap_or_else(|_| panic!("Could not read a file with filename {file_name}"));
-    machine.state_mut().load_memory(&file_bytes);
+    let game_name = game.map_or_else(
+        || file_stem(&options.game_path),
+        |game| game.name.to_owned(),
+    );
+    let key_inputs = load_key_inputs(&*machine, &game_name)?;
+    let mut emulator = Emulator::new(machine);
+    let mut frame_capture = FrameCapture::from_options(options, &emulator)?;
 
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
 
-    let (screen_width, screen_height) = screen_dimensions(&*machine);
-    let window = video_subsystem
-        .window(machine.name(), screen_width * 4, screen_height * 4)
-        .position_centered()
-        .build()
-        .map_err(|e| e.to_string())?;
+    let (screen_width, screen_height) = emulator.screen_dimensions();
+    let mut window_builder = video_subsystem.window(
+        emulator.machine().name(),
+        screen_width * options.scale,
+        screen_height * options.scale,
+    );
+    window_builder.position_centered();
+    if options.is_fullscreen {
+        window_builder.fullscreen_desktop();
+    }
+    let window = window_builder.build().map_err(|e| e.to_string())?;
 
     let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    // Keeps the screen's aspect ratio when the window is fullscreen
+    canvas
+        .set_logical_size(screen_width, screen_height)
+        .map_err(|e| e.to_string())?;
 
     let texture_creator = canvas.texture_creator();
 
@@ -86,197 +180,661 @@ fn main() -> Result<(), String> {
     canvas.present();
 
     let mut event_pump = sdl_context.event_pump()?;
+    let mut controllers = Controllers::new(sdl_context.game_controller()?);
 
-    let mut timer = Instant::now();
-    let mut current_screen_line: u32 = 0;
-    let mut is_paused = false;
+    let mut next_frame_time = Instant::now();
+    let mut is_paused = options.is_paused;
+    let mut save_state_slots = SaveStateSlots::from_rom_file_name(&options.game_path);
+    let mut rewind_buffer = RewindBuffer::new(REWIND_CAPACITY);
+    let mut is_rewinding = false;
+    let mut rewound_frame_count: u64 = 0;
+    let mut frame_count: u64 = 0;
+    let mut active_player = None;
 
     'running: loop {
-        if !is_paused && !machine.state().is_halted {
-            runner::run_next_operation(machine.state_mut());
+        let was_paused = is_paused;
+        if !is_rewinding {
+            rewound_frame_count = 0;
         }
-
-        let duration = timer.elapsed();
-
-        if duration > Duration::from_micros(1_000_000 / FRAME_RATE / u64::from(screen_height)) {
-            timer = Instant::now();
-            current_screen_line += 1;
-            if !is_paused {
-                generate_video_interrupts_if_needed(
-                    machine.state_mut(),
-                    current_screen_line,
-                    screen_height,
+        if is_rewinding {
+            // Each snapshot is shown for as many frames as were between snapshots, so rewinding
+            // runs at normal speed
+            if rewound_frame_count.is_multiple_of(REWIND_SNAPSHOT_INTERVAL) {
+                rewind_one_snapshot(emulator.machine_mut(), &mut rewind_buffer);
+            }
+            rewound_frame_count += 1;
+            handle_audio_events(
+                &mut emulator,
+                sound_player.as_mut(),
+                audio_recorder.as_mut(),
+            );
+        } else if !is_paused {
+            if let Err(stop) = emulator.run_frame() {
+                error!("{stop}, pausing emulation");
+                is_paused = true;
+                // The frame never finished, so it is left out of the recordings
+                handle_audio_events(&mut emulator, sound_player.as_mut(), None);
+            } else {
+                handle_audio_events(
+                    &mut emulator,
+                    sound_player.as_mut(),
+                    audio_recorder.as_mut(),
                 );
+                frame_capture.capture(&emulator)?;
+
+                frame_count += 1;
+                if frame_count.is_multiple_of(REWIND_SNAPSHOT_INTERVAL) {
+                    let machine = emulator.machine();
+                    rewind_buffer.push(save_state::save(machine.state(), machine.name(), &[]));
+                }
+                if options.frame_limit == Some(frame_count) {
+                    break 'running;
+                }
             }
         }
 
-        if current_screen_line >= screen_height {
-            current_screen_line = 0;
-            let screen_pixel_data = screen_pixel_data(&*machine);
-            texture
-                .update(
-                    None,
-                    &screen_pixel_data,
-                    screen_width as usize * NUM_PIXEL_COMPONENTS,
-                )
+        if emulator.machine().active_player() != active_player {
+            active_player = emulator.machine().active_player();
+            canvas
+                .window_mut()
+                .set_title(&window_title(emulator.machine()))
                 .map_err(|e| e.to_string())?;
+        }
 
-            render_next_frame(&mut canvas, &texture)?;
-            let should_quit = handle_events(&mut event_pump, &mut machine, &mut is_paused);
-
-            if should_quit {
-                break 'running;
+        texture
+            .update(
+                None,
+                &emulator.framebuffer(),
+                screen_width as usize * NUM_PIXEL_COMPONENTS,
+            )
+            .map_err(|e| e.to_string())?;
+
+        render_next_frame(&mut canvas, &texture)?;
+        let handled_events = handle_events(
+            &mut event_pump,
+            &mut emulator,
+            &key_inputs,
+            &mut controllers,
+            &mut is_paused,
+            &mut save_state_slots,
+            &mut is_rewinding,
+        );
+
+        if handled_events.should_take_screenshot {
+            take_screenshot(&emulator, &options.game_path);
+        }
+        if handled_events.should_quit {
+            break 'running;
+        }
+        // Stops looped sounds droning on while paused
+        if is_paused != was_paused && !options.is_muted {
+            if is_paused {
+                Channel::all().pause();
+            } else {
+                Channel::all().resume();
             }
-
-            machine.set_ports_from_inputs();
         }
+
+        wait_for_next_frame(&mut next_frame_time);
     }
 
-    Ok(())
+    save_audio_recording(options, audio_recorder.as_ref())?;
+    frame_capture.finish(options, &emulator)
 }
 
-fn screen_dimensions(machine: &dyn Machine) -> (u32, u32) {
-    let screen_orientation = machine.orientation();
-    let is_screen_rotated = (screen_orientation % 360 / 90) % 2 == 1;
-    let screen_width = if is_screen_rotated {
-        ORIGINAL_SCREEN_HEIGHT
+// Creates the machine for the game and loads its ROMs and DIP switch settings
+fn load_machine(
+    options: &PlayOptions,
+) -> Result<(Box<dyn Machine>, Option<&'static Game>), String> {
+    let file_name = options.game_path.as_str();
+    let game_path = Path::new(file_name);
+    let is_rom_set = game_path.is_dir() || file_name.ends_with(".zip");
+    let file_stem = file_stem(file_name);
+    let (rom_files, rom_image) = if is_rom_set {
+        let rom_files = rom_set::read_rom_files(game_path).map_err(|e| e.to_string())?;
+        (rom_files, Vec::new())
     } else {
-        ORIGINAL_SCREEN_WIDTH
+        (HashMap::new(), read_file(file_name)?)
+    };
+
+    // A game is recognised by the checksums of its ROMs, or failing that by its file name
+    let game = match &options.machine_name {
+        Some(machine_name) => Some(games::find_by_name(machine_name).ok_or_else(|| {
+            format!("Unknown machine {machine_name}, use --list-machines to see them")
+        })?),
+        None if is_rom_set => games::identify_rom_files(&rom_files),
+        None => games::identify_rom_image(&rom_image),
+    }
+    .or_else(|| games::find_by_name(&file_stem));
+
+    let mut machine = match game {
+        Some(game) => {
+            info!("Running {} as {}", file_name, game.title);
+            game.create_machine()
+        }
+        None => {
+            warn!("{file_name} is not a known game, so it will run without any inputs or sound");
+            Box::new(BlankMachine::from_name_and_orientation(
+                file_stem.clone(),
+                0,
+            ))
+        }
     };
-    let screen_height = if is_screen_rotated {
-        ORIGINAL_SCREEN_WIDTH
+
+    if is_rom_set {
+        let rom_set = game.and_then(|game| game.rom_set).ok_or_else(|| {
+            format!("No ROM set is known for {file_stem}, use --machine to choose a machine")
+        })?;
+        let bad_dumps = rom_set
+            .load(machine.state_mut(), &rom_files)
+            .map_err(|e| e.to_string())?;
+        for bad_dump in bad_dumps {
+            warn!(
+                "{bad_dump} is a bad dump, as its checksum does not match, so the game may not run correctly"
+            );
+        }
     } else {
-        ORIGINAL_SCREEN_HEIGHT
+        machine.state_mut().load_memory(&rom_image);
+    }
+
+    for (name, value) in &options.dip_settings {
+        machine
+            .set_dip_switch(name, value)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok((machine, game))
+}
+
+// Plays the sounds triggered since the last call, and adds them to the recording if there is one
+fn handle_audio_events(
+    emulator: &mut Emulator,
+    mut sound_player: Option<&mut SoundPlayer>,
+    audio_recorder: Option<&mut AudioRecorder>,
+) {
+    let audio_events = emulator.audio_events();
+    if let Some(audio_recorder) = audio_recorder {
+        let state_count = emulator.machine().state().cpu_total_state_count();
+        audio_recorder.record(&audio_events, state_count);
+    }
+    if let Some(sound_player) = &mut sound_player {
+        for timed_event in audio_events {
+            sound_player.play(timed_event.event);
+        }
+    }
+}
+
+// Runs as fast as possible, with no window or sound
+fn run_headless(
+    emulator: &mut Emulator,
+    frame_limit: u64,
+    mut audio_recorder: Option<&mut AudioRecorder>,
+    frame_capture: &mut FrameCapture,
+) -> Result<(), String> {
+    let start_time = Instant::now();
+    for _ in 0..frame_limit {
+        emulator.run_frame().map_err(|stop| stop.to_string())?;
+
+        let audio_events = emulator.audio_events();
+        if let Some(audio_recorder) = &mut audio_recorder {
+            let state_count = emulator.machine().state().cpu_total_state_count();
+            audio_recorder.record(&audio_events, state_count);
+        }
+        frame_capture.capture(emulator)?;
+    }
+    info!(
+        "Ran {frame_limit} frames in {:.2} seconds",
+        start_time.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+fn machine_listing() -> String {
+    let mut listing = String::new();
+    for game in &games::KNOWN_GAMES {
+        let machine = game.create_machine();
+        listing += &format!(
+            "{}: {}\n  Orientation: {} degrees, colour overlay: {}, ports: {:?}\n",
+            game.name,
+            game.title,
+            machine.orientation(),
+            if machine.overlay().is_empty() {
+                "no"
+            } else {
+                "yes"
+            },
+            game.port_layout,
+        );
+        if let Some(rom_set) = game.rom_set {
+            let file_names: Vec<&str> = rom_set
+                .files
+                .iter()
+                .map(|rom_file| rom_file.file_name)
+                .collect();
+            listing += &format!("  ROM set: {}\n", file_names.join(", "));
+        }
+    }
+    listing
+}
+
+fn dip_switch_listing(machine: &dyn Machine) -> String {
+    let Some(dip_switches) = machine.dip_switches() else {
+        return format!("{} has no DIP switches\n", machine.name());
     };
-    (screen_width, screen_height)
+
+    let mut listing = String::new();
+    for definition in dip_switches.definitions() {
+        listing += &format!(
+            "{}: {}\n  Values: {} (currently {})\n",
+            definition.name,
+            definition.description,
+            definition.value_names().join(", "),
+            dip_switches.value(definition.name).unwrap_or("unknown"),
+        );
+    }
+    listing
 }
 
-fn raise_interrupt(state: &mut State, reset_index: u8) {
-    if state.are_interrupts_enabled {
-        state.is_halted = false;
-        debug!("-- Raised interrupt with reset index of {} --", reset_index);
-        emu_8080::branch_instructions::rst_instruction(state, reset_index);
+fn window_title(machine: &dyn Machine) -> String {
+    match machine.active_player() {
+        Some(player) => format!("{} - Player {player}", machine.name()),
+        None => machine.name().to_owned(),
     }
 }
 
-fn generate_video_interrupts_if_needed(state: &mut State, screen_line: u32, max_screen_line: u32) {
-    // From http://computerarcheology.com/Arcade/SpaceInvaders/Hardware.html
-    if screen_line == 96 {
-        raise_interrupt(state, 1);
-    } else if screen_line == max_screen_line {
-        raise_interrupt(state, 2);
+// Emulated time only syncs to real time once per frame. If the host falls too far behind,
+// the emulation slows down rather than trying to catch up.
+fn wait_for_next_frame(next_frame_time: &mut Instant) {
+    let frame_duration = Duration::from_nanos(1_000_000_000 / scheduler::FRAME_RATE as u64);
+    let now = Instant::now();
+
+    if *next_frame_time > now {
+        thread::sleep(*next_frame_time - now);
+    } else if now - *next_frame_time > frame_duration * MAX_FRAMES_BEHIND {
+        *next_frame_time = now;
     }
+
+    *next_frame_time += frame_duration;
 }
 
-fn screen_pixel_data(machine: &dyn Machine) -> [u8; SCREEN_DATA_SIZE] {
-    const VIDEO_MEMORY_START: u16 = 0x2400;
-    let num_bytes_per_original_row: u32 = ORIGINAL_SCREEN_WIDTH / 8;
-    let mut screen_pixel_data = [0b0000_0000; SCREEN_DATA_SIZE];
-    let state = machine.state();
-
-    let (screen_width, screen_height) = screen_dimensions(machine);
-    let screen_orientation = machine.orientation();
-
-    for original_screen_row in 0..ORIGINAL_SCREEN_HEIGHT {
-        for original_screen_column_byte in 0..num_bytes_per_original_row {
-            let memory_address = VIDEO_MEMORY_START
-                + (original_screen_row * num_bytes_per_original_row + original_screen_column_byte)
-                    as u16;
-            let memory_value = state.memory[memory_address as usize];
-
-            if memory_value != 0b0000_0000 {
-                set_original_column_byte_pixels(
-                    machine,
-                    &mut screen_pixel_data,
-                    screen_width,
-                    screen_height,
-                    screen_orientation,
-                    original_screen_column_byte,
-                    original_screen_row,
-                    memory_value,
-                );
+fn render_next_frame(canvas: &mut WindowCanvas, texture: &Texture) -> Result<(), String> {
+    canvas.clear();
+    canvas.copy(texture, None, None)?;
+    canvas.present();
+    Ok(())
+}
+
+// The oldest snapshot is kept, so that holding the rewind key past it stays on that frame
+fn rewind_one_snapshot(machine: &mut dyn Machine, rewind_buffer: &mut RewindBuffer) {
+    if let Some(snapshot) = rewind_buffer.pop() {
+        let machine_name = machine.name().to_owned();
+        if let Err(e) = save_state::load(machine.state_mut(), &machine_name, &snapshot) {
+            error!("Could not rewind: {e}");
+        }
+
+        if rewind_buffer.is_empty() {
+            rewind_buffer.push(snapshot);
+        }
+    }
+}
+
+struct SaveStateSlots {
+    file_stem: String,
+    current_slot: u8,
+}
+
+impl SaveStateSlots {
+    fn from_rom_file_name(rom_file_name: &str) -> Self {
+        SaveStateSlots {
+            file_stem: Path::new(rom_file_name)
+                .with_extension("")
+                .to_string_lossy()
+                .into_owned(),
+            current_slot: 0,
+        }
+    }
+
+    fn path(&self) -> String {
+        format!("{}.state{}", self.file_stem, self.current_slot)
+    }
+
+    fn change_slot(&mut self, is_next: bool) {
+        self.current_slot = if is_next {
+            (self.current_slot + 1) % NUM_SAVE_STATE_SLOTS
+        } else {
+            (self.current_slot + NUM_SAVE_STATE_SLOTS - 1) % NUM_SAVE_STATE_SLOTS
+        };
+        info!("Selected save state slot {}", self.current_slot);
+    }
+
+    fn save(&self, machine: &dyn Machine) {
+        let bytes = save_state::save(machine.state(), machine.name(), &[]);
+
+        match fs::write(self.path(), bytes) {
+            Ok(()) => info!("Saved state to slot {}", self.current_slot),
+            Err(e) => error!("Could not write save state {}: {e}", self.path()),
+        }
+    }
+
+    fn load(&self, machine: &mut dyn Machine) {
+        let bytes = match fs::read(self.path()) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("Could not read save state {}: {e}", self.path());
+                return;
             }
+        };
+
+        let machine_name = machine.name().to_owned();
+        match save_state::load(machine.state_mut(), &machine_name, &bytes) {
+            Ok(_) => info!("Loaded state from slot {}", self.current_slot),
+            Err(e) => error!("Could not load save state {}: {e}", self.path()),
         }
     }
+}
 
-    screen_pixel_data
+const AUDIO_FOLDER_PATH: &str = "audio/";
+
+const SPACE_INVADERS_SOUND_FILES: [(SoundName, &str); 9] = [
+    (SoundName::Shoot, "shoot.wav"),
+    (SoundName::PlayerKilled, "explosion.wav"),
+    (SoundName::InvaderKilled, "invaderkilled.wav"),
+    (SoundName::UfoFly, "ufo_lowpitch.wav"),
+    (SoundName::UfoKilled, "ufo_highpitch.wav"),
+    (SoundName::InvaderMovement1, "fastinvader1.wav"),
+    (SoundName::InvaderMovement2, "fastinvader2.wav"),
+    (SoundName::InvaderMovement3, "fastinvader3.wav"),
+    (SoundName::InvaderMovement4, "fastinvader4.wav"),
+];
+
+const BOOT_HILL_SOUND_FILES: [(SoundName, &str); 4] = [
+    (SoundName::LeftShot, "boothill_left_shot.wav"),
+    (SoundName::RightShot, "boothill_right_shot.wav"),
+    (SoundName::LeftHit, "boothill_left_hit.wav"),
+    (SoundName::RightHit, "boothill_right_hit.wav"),
+];
+
+// Each sound has its own channel, like the separate sound circuits on the boards, so a sound
+// playing again restarts it rather than cutting off a different one. The tone has the first channel.
+const TONE_CHANNEL: Channel = Channel(0);
+
+fn sound_channel(sound_name: SoundName) -> Channel {
+    Channel(sound_name.into_usize() as i32 + 1)
 }
 
-#[allow(clippy::too_many_arguments)]
-fn set_original_column_byte_pixels(
-    machine: &dyn Machine,
-    screen_pixel_data: &mut [u8; SCREEN_DATA_SIZE],
-    screen_width: u32,
-    screen_height: u32,
-    screen_orientation: u32,
-    original_screen_column_byte: u32,
-    original_screen_row: u32,
-    memory_value: u8,
-) {
-    let num_screen_turns = screen_orientation % 360 / 90;
-
-    for bit_index in 0_u8..=7_u8 {
-        let is_bit_set = emu_8080::bit_operations::is_bit_set(memory_value, bit_index);
-
-        if is_bit_set {
-            let original_screen_column = original_screen_column_byte * 8 + u32::from(bit_index);
-
-            let (x, y) = match num_screen_turns {
-                0 => (original_screen_column, original_screen_row),
-                1 => (
-                    (screen_width - 1) - original_screen_row,
-                    original_screen_column,
-                ),
-                2 => (
-                    (screen_width - 1) - original_screen_column,
-                    (screen_height - 1) - original_screen_row,
-                ),
-                3 => (
-                    original_screen_row,
-                    (screen_height - 1) - original_screen_column,
-                ),
-                _ => unreachable!(),
+#[derive(Default)]
+struct SoundPlayer {
+    sound_chunks: HashMap<SoundName, Chunk>,
+    tone_chunk: Option<Chunk>,
+    // As a percentage
+    volume: u8,
+}
+
+impl SoundPlayer {
+    // Sounds without a sample file are synthesized instead, if the emulator knows how to
+    fn from_sound_files(sound_files: &[(SoundName, &str)], volume: u8) -> Self {
+        let mut sound_player = SoundPlayer {
+            volume,
+            ..SoundPlayer::default()
+        };
+
+        for (sound_name, file_name) in sound_files {
+            let path = AUDIO_FOLDER_PATH.to_owned() + file_name;
+            let sound_chunk = Chunk::from_file(&path).or_else(|e| {
+                let samples = synth::synthesize(*sound_name);
+                if samples.is_empty() {
+                    return Err(e);
+                }
+                info!("Could not load sound {path}, so it will be synthesized: {e}");
+                Chunk::from_raw_buffer(samples.into_boxed_slice())
+            });
+
+            match sound_chunk {
+                Ok(sound_chunk) => {
+                    let sound_chunk = sound_player.with_volume(sound_chunk);
+                    sound_player.sound_chunks.insert(*sound_name, sound_chunk);
+                }
+                Err(e) => warn!("Could not load sound {path}: {e}"),
+            }
+        }
+
+        sound_player
+    }
+
+    fn with_volume(&self, mut sound_chunk: Chunk) -> Chunk {
+        sound_chunk.set_volume(mixer::MAX_VOLUME * i32::from(self.volume) / 100);
+        sound_chunk
+    }
+
+    fn play(&mut self, audio_event: AudioEvent) {
+        match audio_event {
+            AudioEvent::Play(sound_name) => {
+                if let Some(sound_chunk) = self.sound_chunks.get(&sound_name) {
+                    let loops = match sound_name.kind() {
+                        SoundKind::OneShot => 0,
+                        SoundKind::Looped => -1,
+                    };
+                    let _sound_result = sound_channel(sound_name).play(sound_chunk, loops);
+                }
+            }
+            AudioEvent::Stop(sound_name) => sound_channel(sound_name).halt(),
+            AudioEvent::SetTone(frequency) => {
+                // The old chunk must stop playing before it is freed
+                TONE_CHANNEL.halt();
+                self.tone_chunk = None;
+
+                let Some(frequency) = frequency else {
+                    return;
+                };
+                let samples = synth::square_wave(frequency, synth::TONE_AMPLITUDE);
+                match Chunk::from_raw_buffer(samples.into_boxed_slice()) {
+                    Ok(tone_chunk) => {
+                        let tone_chunk = self.with_volume(tone_chunk);
+                        let _sound_result = TONE_CHANNEL.play(&tone_chunk, -1);
+                        self.tone_chunk = Some(tone_chunk);
+                    }
+                    Err(e) => warn!("Could not play a tone of {frequency} Hz: {e}"),
+                }
+            }
+        }
+    }
+}
+
+// Records the same sounds as are played, from their sample files where possible
+fn recording_mixer(sound_files: &[(SoundName, &str)], volume: u8) -> synth::Mixer {
+    let mut recording_mixer = synth::Mixer::default();
+    recording_mixer.set_volume(volume);
+
+    for (sound_name, file_name) in sound_files {
+        let path = AUDIO_FOLDER_PATH.to_owned() + file_name;
+        let Ok(bytes) = fs::read(&path) else {
+            continue;
+        };
+        match wav::decode(&bytes) {
+            Ok(samples) => recording_mixer.set_sound(*sound_name, samples),
+            Err(e) => warn!("Could not record sound {path}, so it will be synthesized: {e}"),
+        }
+    }
+
+    recording_mixer
+}
+
+fn save_audio_recording(
+    options: &PlayOptions,
+    audio_recorder: Option<&AudioRecorder>,
+) -> Result<(), String> {
+    if let (Some(path), Some(audio_recorder)) = (&options.audio_recording_path, audio_recorder) {
+        fs::write(path, audio_recorder.wav_bytes())
+            .map_err(|e| format!("Could not write the audio recording {path}: {e}"))?;
+        info!(
+            "Saved {:.1} seconds of audio to {path}",
+            audio_recorder.samples().len() as f64 / f64::from(synth::SAMPLE_RATE)
+        );
+    }
+    Ok(())
+}
+
+// Screenshots are numbered, and written next to the game file like save states
+fn take_screenshot(emulator: &Emulator, game_path: &str) {
+    let file_stem = Path::new(game_path).with_extension("");
+    let Some(path) = (0..)
+        .map(|number| format!("{}_{number:03}.png", file_stem.to_string_lossy()))
+        .find(|path| !Path::new(path).exists())
+    else {
+        return;
+    };
+
+    match write_png(emulator, Path::new(&path)) {
+        Ok(()) => info!("Saved a screenshot to {path}"),
+        Err(e) => error!("{e}"),
+    }
+}
+
+fn write_png(emulator: &Emulator, path: &Path) -> Result<(), String> {
+    let (screen_width, screen_height) = emulator.screen_dimensions();
+    let bytes = png::encode(screen_width, screen_height, &emulator.framebuffer())
+        .map_err(|e| e.to_string())?;
+    fs::write(path, bytes).map_err(|e| format!("Could not write {}: {e}", path.display()))
+}
+
+// Many GIF viewers slow down frames shorter than 2/100ths of a second, so only every other
+// frame is kept in a GIF
+const GIF_FRAME_INTERVAL: u64 = 2;
+
+// Saves the emulated frames as numbered PNG files, or as an animated GIF
+struct FrameCapture {
+    frame_dump_folder: Option<PathBuf>,
+    gif_encoder: Option<GifEncoder>,
+    num_frames: u64,
+}
+
+impl FrameCapture {
+    fn from_options(options: &PlayOptions, emulator: &Emulator) -> Result<Self, String> {
+        if let Some(folder) = &options.frame_dump_folder {
+            fs::create_dir_all(folder)
+                .map_err(|e| format!("Could not create the folder {folder}: {e}"))?;
+        }
+
+        let (screen_width, screen_height) = emulator.screen_dimensions();
+        Ok(FrameCapture {
+            frame_dump_folder: options.frame_dump_folder.as_ref().map(PathBuf::from),
+            gif_encoder: options
+                .gif_recording_path
+                .as_ref()
+                .map(|_| GifEncoder::new(screen_width as u16, screen_height as u16)),
+            num_frames: 0,
+        })
+    }
+
+    fn capture(&mut self, emulator: &Emulator) -> Result<(), String> {
+        if let Some(folder) = &self.frame_dump_folder {
+            write_png(
+                emulator,
+                &folder.join(format!("frame_{:05}.png", self.num_frames)),
+            )?;
+        }
+
+        if let Some(gif_encoder) = &mut self.gif_encoder
+            && self.num_frames.is_multiple_of(GIF_FRAME_INTERVAL)
+        {
+            // Delays are in whole hundredths of a second, so they vary to keep in time overall
+            let gif_frame_number = self.num_frames / GIF_FRAME_INTERVAL;
+            let frame_time = |gif_frame_number: u64| {
+                gif_frame_number * GIF_FRAME_INTERVAL * 100 / scheduler::FRAME_RATE as u64
             };
+            let delay = frame_time(gif_frame_number + 1) - frame_time(gif_frame_number);
+            gif_encoder
+                .add_frame(&emulator.framebuffer(), delay as u16)
+                .map_err(|e| e.to_string())?;
+        }
 
-            let index = (y * screen_width + x) as usize * NUM_PIXEL_COMPONENTS;
+        self.num_frames += 1;
+        Ok(())
+    }
 
-            set_pixel(
-                machine,
-                &mut screen_pixel_data[index..(index + NUM_PIXEL_COMPONENTS)],
-                x,
-                y,
+    // The screenshot is of the last frame shown
+    fn finish(self, options: &PlayOptions, emulator: &Emulator) -> Result<(), String> {
+        if let (Some(path), Some(gif_encoder)) = (&options.gif_recording_path, self.gif_encoder) {
+            fs::write(path, gif_encoder.finish())
+                .map_err(|e| format!("Could not write {path}: {e}"))?;
+            info!("Saved {} frames to {path}", self.num_frames);
+        }
+        if let Some(frame_dump_folder) = &self.frame_dump_folder {
+            info!(
+                "Saved {} frames to {}",
+                self.num_frames,
+                frame_dump_folder.display()
             );
         }
+        if let Some(path) = &options.screenshot_path {
+            write_png(emulator, Path::new(path))?;
+            info!("Saved a screenshot to {path}");
+        }
+        Ok(())
     }
 }
 
-fn set_pixel(machine: &dyn Machine, pixel_slice: &mut [u8], x: u32, y: u32) {
-    let color = machine.pixel_color(x, y);
-    pixel_slice[0] = color.r;
-    pixel_slice[1] = color.g;
-    pixel_slice[2] = color.b;
+// Keys come from the key bindings file in the working directory if there is one, otherwise the
+// defaults built into the emulator. Only the machine's own input actions are bound.
+fn load_key_inputs(
+    machine: &dyn Machine,
+    game_name: &str,
+) -> Result<HashMap<Keycode, Input>, String> {
+    let config_text = if Path::new(KEY_BINDINGS_FILE_NAME).exists() {
+        fs::read_to_string(KEY_BINDINGS_FILE_NAME)
+            .map_err(|e| format!("Could not read {KEY_BINDINGS_FILE_NAME}: {e}"))?
+    } else {
+        DEFAULT_KEY_BINDINGS.to_owned()
+    };
+    let config = KeyBindingsConfig::parse(&config_text)
+        .map_err(|e| format!("Invalid key bindings in {KEY_BINDINGS_FILE_NAME}: {e}"))?;
+
+    let key_bindings = config.bindings_for_game(game_name);
+    let mut key_inputs = HashMap::new();
+
+    for input in machine.input_actions() {
+        let key_names = key_bindings.keys_for_input(*input);
+        for key_name in key_names {
+            let key = Keycode::from_name(key_name)
+                .ok_or_else(|| format!("Unknown key `{key_name}` for {}", input.name()))?;
+            key_inputs.insert(key, *input);
+        }
+        info!("{}: {}", input.name(), key_names.join(", "));
+    }
+
+    Ok(key_inputs)
 }
 
-fn render_next_frame(canvas: &mut WindowCanvas, texture: &Texture) -> Result<(), String> {
-    canvas.clear();
-    canvas.copy(texture, None, None)?;
-    canvas.present();
-    Ok(())
+#[derive(Default)]
+struct HandledEvents {
+    should_quit: bool,
+    should_take_screenshot: bool,
 }
 
 fn handle_events(
     event_pump: &mut EventPump,
-    machine: &mut Box<dyn Machine>,
+    emulator: &mut Emulator,
+    key_inputs: &HashMap<Keycode, Input>,
+    controllers: &mut Controllers,
     is_paused: &mut bool,
-) -> bool {
+    save_state_slots: &mut SaveStateSlots,
+    is_rewinding: &mut bool,
+) -> HandledEvents {
+    let mut handled_events = HandledEvents::default();
+
     for event in event_pump.poll_iter() {
+        for (input, is_pressed) in controllers.handle_event(&event) {
+            emulator.set_input(input, is_pressed);
+        }
+
         match event {
             Event::Quit { .. }
             | Event::KeyDown {
                 keycode: Some(Keycode::Escape),
                 ..
             } => {
-                return true;
+                handled_events.should_quit = true;
+                return handled_events;
             }
             Event::KeyDown {
                 keycode: Some(Keycode::P),
@@ -284,19 +842,51 @@ fn handle_events(
             } => {
                 *is_paused = !*is_paused;
             }
+            Event::KeyDown {
+                keycode: Some(Keycode::F5),
+                ..
+            } => save_state_slots.save(emulator.machine()),
+            Event::KeyDown {
+                keycode: Some(Keycode::F9),
+                ..
+            } => save_state_slots.load(emulator.machine_mut()),
+            Event::KeyDown {
+                keycode: Some(Keycode::F6),
+                ..
+            } => save_state_slots.change_slot(false),
+            Event::KeyDown {
+                keycode: Some(Keycode::F7),
+                ..
+            } => save_state_slots.change_slot(true),
+            Event::KeyDown {
+                keycode: Some(Keycode::F12),
+                ..
+            } => handled_events.should_take_screenshot = true,
+            Event::KeyDown {
+                keycode: Some(Keycode::F3),
+                ..
+            } => *is_rewinding = true,
+            Event::KeyUp {
+                keycode: Some(Keycode::F3),
+                ..
+            } => *is_rewinding = false,
             Event::KeyDown {
                 keycode: Some(key), ..
             } => {
-                machine.set_input_from_key(key, true);
+                if let Some(input) = key_inputs.get(&key) {
+                    emulator.set_input(*input, true);
+                }
             }
             Event::KeyUp {
                 keycode: Some(key), ..
             } => {
-                machine.set_input_from_key(key, false);
+                if let Some(input) = key_inputs.get(&key) {
+                    emulator.set_input(*input, false);
+                }
             }
             _ => {}
         }
     }
 
-    false
+    handled_events
 }
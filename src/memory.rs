@@ -0,0 +1,117 @@
+use crate::{MEMORY_SIZE, Memory};
+
+pub struct FlatMemory {
+    bytes: Box<[u8; MEMORY_SIZE]>,
+}
+
+impl Default for FlatMemory {
+    fn default() -> Self {
+        FlatMemory {
+            bytes: Box::new([0; MEMORY_SIZE]),
+        }
+    }
+}
+
+impl Memory for FlatMemory {
+    fn read(&self, address: u16) -> u8 {
+        self.bytes[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.bytes[address as usize] = value;
+    }
+}
+
+// From MAME's mw8080bw driver. The top address line isn't decoded, so the map repeats every 32K.
+// 8K of ROM at 0x0000 is followed by 8K of RAM at 0x2000. Some boards have another 8K of ROM at
+// 0x4000, and the RAM is repeated at 0x6000.
+const MIDWAY_ADDRESS_MASK: u16 = 0x7FFF;
+const MIDWAY_RAM: std::ops::Range<usize> = 0x2000..0x4000;
+const MIDWAY_RAM_MIRROR_START: usize = 0x6000;
+
+pub struct MidwayMemory {
+    bytes: Box<[u8; MIDWAY_RAM_MIRROR_START]>,
+}
+
+impl Default for MidwayMemory {
+    fn default() -> Self {
+        MidwayMemory {
+            bytes: Box::new([0; MIDWAY_RAM_MIRROR_START]),
+        }
+    }
+}
+
+impl MidwayMemory {
+    fn index(address: u16) -> usize {
+        let index = (address & MIDWAY_ADDRESS_MASK) as usize;
+        if index >= MIDWAY_RAM_MIRROR_START {
+            index - MIDWAY_RAM_MIRROR_START + MIDWAY_RAM.start
+        } else {
+            index
+        }
+    }
+}
+
+impl Memory for MidwayMemory {
+    fn read(&self, address: u16) -> u8 {
+        self.bytes[MidwayMemory::index(address)]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        let index = MidwayMemory::index(address);
+        if MIDWAY_RAM.contains(&index) {
+            self.bytes[index] = value;
+        }
+    }
+
+    fn poke(&mut self, address: u16, value: u8) {
+        self.bytes[MidwayMemory::index(address)] = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_memory_reads_back_every_write() {
+        let mut memory = FlatMemory::default();
+        memory.write(0x0000, 0x12);
+        memory.write(0xFFFF, 0x34);
+        assert_eq!(memory.read(0x0000), 0x12);
+        assert_eq!(memory.read(0xFFFF), 0x34);
+        assert_eq!(memory.read(0x4000), 0x00);
+    }
+
+    #[test]
+    fn midway_memory_ignores_writes_to_rom() {
+        let mut memory = MidwayMemory::default();
+        memory.poke(0x1000, 0xC3);
+        memory.write(0x1000, 0x00);
+        memory.write(0x5000, 0x00);
+        assert_eq!(memory.read(0x1000), 0xC3);
+    }
+
+    #[test]
+    fn midway_memory_mirrors_ram_at_0x6000() {
+        let mut memory = MidwayMemory::default();
+        memory.write(0x6400, 0xAA);
+        assert_eq!(memory.read(0x2400), 0xAA);
+        assert_eq!(memory.read(0xE400), 0xAA);
+        assert_eq!(memory.read(0x0400), 0x00);
+
+        memory.write(0x3FFF, 0xBB);
+        assert_eq!(memory.read(0x7FFF), 0xBB);
+    }
+
+    #[test]
+    fn midway_memory_has_rom_at_0x4000() {
+        let mut memory = MidwayMemory::default();
+        memory.poke(0x4000, 0xC3);
+        memory.write(0x4000, 0x00);
+        assert_eq!(memory.read(0x4000), 0xC3);
+        assert_eq!(memory.read(0xC000), 0xC3);
+        assert_eq!(memory.read(0x0000), 0x00);
+        assert_eq!(memory.read(0x2000), 0x00);
+    }
+}
@@ -0,0 +1,383 @@
+use std::str::FromStr;
+
+use log::LevelFilter;
+
+pub const USAGE: &str = "\
+Usage: emu-8080 [OPTIONS] <GAME>
+       emu-8080 --disassemble <ROM>
+       emu-8080 --disassemble-traced <ROM>
+       emu-8080 --debug <ROM>
+       emu-8080 --list-machines
+
+<GAME> is a ROM set folder or .zip file, or a single ROM image such as invaders.bin.
+
+Options:
+  --machine <NAME>        Emulate the named machine, instead of recognising it from the ROMs
+  --dip <NAME>=<VALUE>    Set a DIP switch, can be given more than once
+  --list-dip-switches     List the machine's DIP switches and exit
+  --scale <N>             Scale the window by N times the original screen size [default: 4]
+  --fullscreen            Start in fullscreen
+  --paused                Start with the emulation paused
+  --mute                  Play without sound
+  --volume <0-100>        Sound volume as a percentage [default: 50]
+  --record-audio <FILE>   Record the game's sound to a .wav file, in time with the emulation
+  --screenshot <FILE>     Save a PNG screenshot of the last frame on exit
+  --dump-frames <FOLDER>  Save every frame as a numbered PNG file in the folder
+  --record-gif <FILE>     Record the screen to an animated GIF
+  --frames <N>            Exit after running N frames
+  --headless              Run without a window or sound, as fast as possible. Needs --frames
+  --log-level <LEVEL>     One of off, error, warn, info, debug or trace [default: RUST_LOG, or error]
+  -h, --help              Print this help
+";
+
+const DEFAULT_SCALE: u32 = 4;
+const DEFAULT_VOLUME: u8 = 50;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Options {
+    pub command: Command,
+    // Overrides RUST_LOG when set
+    pub log_level: Option<LevelFilter>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Command {
+    Help,
+    ListMachines,
+    Disassemble(String),
+    DisassembleTraced(String),
+    Debug(String),
+    Play(PlayOptions),
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PlayOptions {
+    pub game_path: String,
+    pub machine_name: Option<String>,
+    pub dip_settings: Vec<(String, String)>,
+    pub should_list_dip_switches: bool,
+    pub scale: u32,
+    pub is_fullscreen: bool,
+    pub is_paused: bool,
+    pub is_muted: bool,
+    // As a percentage
+    pub volume: u8,
+    pub audio_recording_path: Option<String>,
+    pub screenshot_path: Option<String>,
+    pub frame_dump_folder: Option<String>,
+    pub gif_recording_path: Option<String>,
+    pub frame_limit: Option<u64>,
+    pub is_headless: bool,
+}
+
+pub fn parse(args: &[String]) -> Result<Options, String> {
+    let mut command = None;
+    let mut game_path = None;
+    let mut log_level = None;
+    // Play options are only checked once the command is known
+    let mut first_play_option = None;
+    let mut play_options = PlayOptions {
+        game_path: String::new(),
+        machine_name: None,
+        dip_settings: Vec::new(),
+        should_list_dip_switches: false,
+        scale: DEFAULT_SCALE,
+        is_fullscreen: false,
+        is_paused: false,
+        is_muted: false,
+        volume: DEFAULT_VOLUME,
+        audio_recording_path: None,
+        screenshot_path: None,
+        frame_dump_folder: None,
+        gif_recording_path: None,
+        frame_limit: None,
+        is_headless: false,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value_for = |option: &str| {
+            args.next()
+                .ok_or_else(|| format!("{option} needs a value"))
+                .cloned()
+        };
+
+        let mode = match arg.as_str() {
+            "-h" | "--help" => {
+                return Ok(Options {
+                    command: Command::Help,
+                    log_level,
+                });
+            }
+            "--list-machines" => Some(Command::ListMachines),
+            "--disassemble" => Some(Command::Disassemble(value_for(arg)?)),
+            "--disassemble-traced" => Some(Command::DisassembleTraced(value_for(arg)?)),
+            "--debug" => Some(Command::Debug(value_for(arg)?)),
+            "--machine" => {
+                play_options.machine_name = Some(value_for(arg)?);
+                None
+            }
+            "--dip" => {
+                let setting = value_for(arg)?;
+                let (name, value) = setting.split_once('=').ok_or_else(|| {
+                    format!("Expected a DIP switch setting such as ships=5, found {setting}")
+                })?;
+                play_options
+                    .dip_settings
+                    .push((name.to_owned(), value.to_owned()));
+                None
+            }
+            "--list-dip-switches" => {
+                play_options.should_list_dip_switches = true;
+                None
+            }
+            "--scale" => {
+                play_options.scale = parse_number(arg, &value_for(arg)?, 1..=16)?;
+                None
+            }
+            "--fullscreen" => {
+                play_options.is_fullscreen = true;
+                None
+            }
+            "--paused" => {
+                play_options.is_paused = true;
+                None
+            }
+            "--mute" => {
+                play_options.is_muted = true;
+                None
+            }
+            "--volume" => {
+                play_options.volume = parse_number(arg, &value_for(arg)?, 0..=100)?;
+                None
+            }
+            "--record-audio" => {
+                play_options.audio_recording_path = Some(value_for(arg)?);
+                None
+            }
+            "--screenshot" => {
+                play_options.screenshot_path = Some(value_for(arg)?);
+                None
+            }
+            "--dump-frames" => {
+                play_options.frame_dump_folder = Some(value_for(arg)?);
+                None
+            }
+            "--record-gif" => {
+                play_options.gif_recording_path = Some(value_for(arg)?);
+                None
+            }
+            "--frames" => {
+                let frames = value_for(arg)?;
+                play_options.frame_limit = Some(
+                    frames
+                        .parse()
+                        .ok()
+                        .filter(|frames| *frames > 0)
+                        .ok_or_else(|| {
+                            format!("--frames must be a positive number, found {frames}")
+                        })?,
+                );
+                None
+            }
+            "--headless" => {
+                play_options.is_headless = true;
+                None
+            }
+            "--log-level" => {
+                let level = value_for(arg)?;
+                log_level = Some(LevelFilter::from_str(&level).map_err(|_| {
+                    format!(
+                        "Unknown log level {level}, expected off, error, warn, info, debug or trace"
+                    )
+                })?);
+                None
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {arg}")),
+            _ => {
+                if let Some(existing_path) = game_path.replace(arg.clone()) {
+                    return Err(format!(
+                        "Only one game can be run, found {existing_path} and {arg}"
+                    ));
+                }
+                None
+            }
+        };
+
+        if let Some(mode) = mode {
+            if command.replace(mode).is_some() {
+                return Err(format!("{arg} can't be combined with another command"));
+            }
+        } else if arg.starts_with('-') && arg != "--log-level" && first_play_option.is_none() {
+            first_play_option = Some(arg.clone());
+        }
+    }
+
+    let command = match (command, game_path) {
+        (Some(_), None) if let Some(option) = first_play_option => {
+            return Err(format!("{option} can only be used when running a game"));
+        }
+        (Some(command), None) => command,
+        (Some(_), Some(game_path)) => return Err(format!("Unexpected argument {game_path}")),
+        (None, None) => return Err("No game file was given".to_owned()),
+        (None, Some(game_path)) => {
+            if play_options.is_headless && play_options.frame_limit.is_none() {
+                return Err("--headless needs --frames, so that the emulator exits".to_owned());
+            }
+            play_options.game_path = game_path;
+            Command::Play(play_options)
+        }
+    };
+
+    Ok(Options { command, log_level })
+}
+
+fn parse_number<T: FromStr + PartialOrd + std::fmt::Display>(
+    option: &str,
+    value: &str,
+    range: std::ops::RangeInclusive<T>,
+) -> Result<T, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|number| range.contains(number))
+        .ok_or_else(|| {
+            format!(
+                "{option} must be a number from {} to {}, found {value}",
+                range.start(),
+                range.end()
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Options, String> {
+        parse(&args.iter().map(|arg| (*arg).to_owned()).collect::<Vec<_>>())
+    }
+
+    fn play_options(args: &[&str]) -> PlayOptions {
+        match parse_args(args).unwrap().command {
+            Command::Play(play_options) => play_options,
+            command => panic!("Expected to play a game, found {command:?}"),
+        }
+    }
+
+    #[test]
+    fn a_game_is_played_with_the_defaults() {
+        let play_options = play_options(&["invaders.zip"]);
+
+        assert_eq!(play_options.game_path, "invaders.zip");
+        assert_eq!(play_options.scale, DEFAULT_SCALE);
+        assert_eq!(play_options.volume, DEFAULT_VOLUME);
+        assert_eq!(play_options.frame_limit, None);
+        assert!(!play_options.is_headless);
+    }
+
+    #[test]
+    fn play_options_can_come_before_or_after_the_game() {
+        let play_options = play_options(&[
+            "--dip",
+            "ships=5",
+            "invaders.zip",
+            "--scale",
+            "2",
+            "--headless",
+            "--frames",
+            "60",
+        ]);
+
+        assert_eq!(
+            play_options.dip_settings,
+            vec![("ships".to_owned(), "5".to_owned())]
+        );
+        assert_eq!(play_options.scale, 2);
+        assert_eq!(play_options.frame_limit, Some(60));
+        assert!(play_options.is_headless);
+    }
+
+    #[test]
+    fn commands_and_the_log_level_are_parsed() {
+        assert_eq!(
+            parse_args(&["--log-level", "info", "--disassemble", "x.bin"]),
+            Ok(Options {
+                command: Command::Disassemble("x.bin".to_owned()),
+                log_level: Some(LevelFilter::Info),
+            })
+        );
+        assert_eq!(
+            parse_args(&["--bad-option", "--help"]),
+            Err("Unknown option --bad-option".to_owned())
+        );
+        assert_eq!(
+            parse_args(&["--help", "--bad-option"]).unwrap().command,
+            Command::Help
+        );
+    }
+
+    #[test]
+    fn values_outside_their_range_are_rejected() {
+        assert_eq!(
+            parse_args(&["invaders.zip", "--scale", "17"]),
+            Err("--scale must be a number from 1 to 16, found 17".to_owned())
+        );
+        assert_eq!(
+            parse_args(&["invaders.zip", "--volume", "loud"]),
+            Err("--volume must be a number from 0 to 100, found loud".to_owned())
+        );
+        assert_eq!(
+            parse_args(&["invaders.zip", "--frames", "0"]),
+            Err("--frames must be a positive number, found 0".to_owned())
+        );
+        assert_eq!(
+            parse_args(&["invaders.zip", "--dip", "ships"]),
+            Err("Expected a DIP switch setting such as ships=5, found ships".to_owned())
+        );
+        assert_eq!(
+            parse_args(&["invaders.zip", "--log-level", "loud"]),
+            Err(
+                "Unknown log level loud, expected off, error, warn, info, debug or trace"
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            parse_args(&["invaders.zip", "--scale"]),
+            Err("--scale needs a value".to_owned())
+        );
+    }
+
+    #[test]
+    fn conflicting_arguments_are_rejected() {
+        assert_eq!(parse_args(&[]), Err("No game file was given".to_owned()));
+        assert_eq!(
+            parse_args(&["invaders.zip", "boothill.bin"]),
+            Err("Only one game can be run, found invaders.zip and boothill.bin".to_owned())
+        );
+        assert_eq!(
+            parse_args(&["--list-machines", "--debug", "x.bin"]),
+            Err("--debug can't be combined with another command".to_owned())
+        );
+        assert_eq!(
+            parse_args(&["--list-machines", "invaders.zip"]),
+            Err("Unexpected argument invaders.zip".to_owned())
+        );
+        assert_eq!(
+            parse_args(&["invaders.zip", "--headless"]),
+            Err("--headless needs --frames, so that the emulator exits".to_owned())
+        );
+    }
+
+    #[test]
+    fn play_options_are_rejected_with_other_commands() {
+        assert_eq!(
+            parse_args(&["--disassemble", "x.bin", "--headless", "--scale", "3"]),
+            Err("--headless can only be used when running a game".to_owned())
+        );
+        assert_eq!(
+            parse_args(&["--dip", "ships=5", "--list-machines"]),
+            Err("--dip can only be used when running a game".to_owned())
+        );
+    }
+}
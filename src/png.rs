@@ -0,0 +1,140 @@
+use crate::checksum;
+
+const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+const RGB_COLOR_TYPE: u8 = 2;
+const NO_FILTER: u8 = 0;
+// The largest stored deflate block
+const MAX_STORED_BLOCK_SIZE: usize = 0xFFFF;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum PngError {
+    WrongImageSize { expected: usize, found: usize },
+}
+
+impl std::fmt::Display for PngError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PngError::WrongImageSize { expected, found } => write!(
+                f,
+                "Expected {expected} bytes of RGB pixels for a PNG image, found {found}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PngError {}
+
+fn write_chunk(bytes: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let checked_start = bytes.len();
+    bytes.extend_from_slice(chunk_type);
+    bytes.extend_from_slice(data);
+    let crc = checksum::crc32(&bytes[checked_start..]);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+}
+
+// A zlib stream of stored deflate blocks. It isn't compressed, but screenshots are small anyway.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK_SIZE).peekable();
+
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        stream.push(u8::from(is_final));
+        stream.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        stream.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&checksum::adler32(data).to_be_bytes());
+    stream
+}
+
+// Takes RGB24 pixels row by row, as from the emulator's framebuffer
+pub fn encode(width: u32, height: u32, rgb_pixels: &[u8]) -> Result<Vec<u8>, PngError> {
+    let row_size = width as usize * 3;
+    if rgb_pixels.len() != row_size * height as usize {
+        return Err(PngError::WrongImageSize {
+            expected: row_size * height as usize,
+            found: rgb_pixels.len(),
+        });
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, then the default compression, filter and interlace methods
+    header.extend_from_slice(&[8, RGB_COLOR_TYPE, 0, 0, 0]);
+
+    // Each row starts with the filter it uses
+    let mut image_data = Vec::with_capacity(rgb_pixels.len() + height as usize);
+    for row in rgb_pixels.chunks_exact(row_size) {
+        image_data.push(NO_FILTER);
+        image_data.extend_from_slice(row);
+    }
+
+    let mut bytes = SIGNATURE.to_vec();
+    write_chunk(&mut bytes, b"IHDR", &header);
+    write_chunk(&mut bytes, b"IDAT", &zlib_stored(&image_data));
+    write_chunk(&mut bytes, b"IEND", &[]);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zip_archive;
+
+    #[test]
+    fn chunks_have_their_length_and_checksum() {
+        let bytes = encode(1, 1, &[0xFF, 0x00, 0x00]).unwrap();
+
+        assert_eq!(&bytes[..8], SIGNATURE);
+        assert_eq!(&bytes[8..16], b"\x00\x00\x00\x0DIHDR");
+        assert_eq!(&bytes[16..29], &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+        assert_eq!(
+            u32::from_be_bytes(bytes[29..33].try_into().unwrap()),
+            checksum::crc32(&bytes[12..29])
+        );
+        assert_eq!(
+            &bytes[bytes.len() - 12..],
+            b"\x00\x00\x00\x00IEND\xAE\x42\x60\x82"
+        );
+    }
+
+    #[test]
+    fn image_data_inflates_to_filtered_rows() {
+        let width = 200;
+        let height = 120;
+        let pixels: Vec<u8> = (0..width * height * 3).map(|index| index as u8).collect();
+        let bytes = encode(width, height, &pixels).unwrap();
+
+        let idat_start = 8 + 25 + 8;
+        let idat_length = u32::from_be_bytes(bytes[33..37].try_into().unwrap()) as usize;
+        let stream = &bytes[idat_start..idat_start + idat_length];
+        let image_data = zip_archive::inflate(&stream[2..stream.len() - 4]).unwrap();
+
+        assert_eq!(image_data.len(), (width * height * 3 + height) as usize);
+        let row_size = (width * 3) as usize;
+        assert_eq!(image_data[0], NO_FILTER);
+        assert_eq!(image_data[1..=row_size], pixels[..row_size]);
+        assert_eq!(
+            &stream[stream.len() - 4..],
+            checksum::adler32(&image_data).to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn images_of_the_wrong_size_are_rejected() {
+        assert_eq!(
+            encode(2, 2, &[0x00; 9]),
+            Err(PngError::WrongImageSize {
+                expected: 12,
+                found: 9
+            })
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+
+// Only the newest snapshot is kept whole. Each older one is stored as the XOR of it with the snapshot
+// after it, run-length encoded, so consecutive frames that share most of their memory stay small.
+pub struct RewindBuffer {
+    capacity: usize,
+    latest_snapshot: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            capacity: capacity.max(1),
+            latest_snapshot: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len() + usize::from(self.latest_snapshot.is_some())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest_snapshot.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest_snapshot = None;
+        self.deltas.clear();
+    }
+
+    // The number of bytes held by the snapshots and deltas
+    pub fn size_in_bytes(&self) -> usize {
+        self.latest_snapshot.as_ref().map_or(0, Vec::len)
+            + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn push(&mut self, snapshot: Vec<u8>) {
+        if let Some(previous_snapshot) = &self.latest_snapshot {
+            self.deltas
+                .push_back(encode_delta(previous_snapshot, &snapshot));
+        }
+        self.latest_snapshot = Some(snapshot);
+
+        if self.len() > self.capacity {
+            self.deltas.pop_front();
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let snapshot = self.latest_snapshot.take()?;
+        self.latest_snapshot = self
+            .deltas
+            .pop_back()
+            .map(|delta| decode_delta(&snapshot, &delta));
+        Some(snapshot)
+    }
+}
+
+// Delta format: the length of the older snapshot, then runs of
+// (number of unchanged bytes, number of changed bytes, XORed changed bytes)
+fn encode_delta(older_snapshot: &[u8], newer_snapshot: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    delta.extend_from_slice(&(older_snapshot.len() as u32).to_le_bytes());
+
+    let xored_bytes: Vec<u8> = (0..older_snapshot.len())
+        .map(|index| older_snapshot[index] ^ newer_snapshot.get(index).copied().unwrap_or(0))
+        .collect();
+    let mut index = 0;
+
+    while index < xored_bytes.len() {
+        let unchanged_start = index;
+        while index < xored_bytes.len() && xored_bytes[index] == 0 {
+            index += 1;
+        }
+        let changed_start = index;
+        while index < xored_bytes.len() && xored_bytes[index] != 0 {
+            index += 1;
+        }
+
+        delta.extend_from_slice(&((changed_start - unchanged_start) as u32).to_le_bytes());
+        delta.extend_from_slice(&((index - changed_start) as u32).to_le_bytes());
+        delta.extend_from_slice(&xored_bytes[changed_start..index]);
+    }
+
+    delta
+}
+
+fn decode_delta(newer_snapshot: &[u8], delta: &[u8]) -> Vec<u8> {
+    let read_u32 = |position: usize| {
+        u32::from_le_bytes(delta[position..position + 4].try_into().unwrap()) as usize
+    };
+
+    let older_length = read_u32(0);
+    let mut older_snapshot: Vec<u8> = (0..older_length)
+        .map(|index| newer_snapshot.get(index).copied().unwrap_or(0))
+        .collect();
+    let mut position = 4;
+    let mut index = 0;
+
+    while position < delta.len() {
+        let unchanged_length = read_u32(position);
+        let changed_length = read_u32(position + 4);
+        position += 8;
+        index += unchanged_length;
+
+        for xored_byte in &delta[position..position + changed_length] {
+            older_snapshot[index] ^= xored_byte;
+            index += 1;
+        }
+        position += changed_length;
+    }
+
+    older_snapshot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pop_returns_snapshots_newest_first() {
+        let mut rewind_buffer = RewindBuffer::new(10);
+        rewind_buffer.push(vec![1, 2, 3, 4]);
+        rewind_buffer.push(vec![1, 5, 3, 4, 9]);
+        rewind_buffer.push(vec![7, 5]);
+
+        assert_eq!(rewind_buffer.len(), 3);
+        assert_eq!(rewind_buffer.pop(), Some(vec![7, 5]));
+        assert_eq!(rewind_buffer.pop(), Some(vec![1, 5, 3, 4, 9]));
+        assert_eq!(rewind_buffer.pop(), Some(vec![1, 2, 3, 4]));
+        assert_eq!(rewind_buffer.pop(), None);
+        assert!(rewind_buffer.is_empty());
+    }
+
+    #[test]
+    fn push_drops_the_oldest_snapshot_when_full() {
+        let mut rewind_buffer = RewindBuffer::new(2);
+        rewind_buffer.push(vec![1]);
+        rewind_buffer.push(vec![2]);
+        rewind_buffer.push(vec![3]);
+
+        assert_eq!(rewind_buffer.len(), 2);
+        assert_eq!(rewind_buffer.pop(), Some(vec![3]));
+        assert_eq!(rewind_buffer.pop(), Some(vec![2]));
+        assert_eq!(rewind_buffer.pop(), None);
+    }
+
+    #[test]
+    fn similar_snapshots_are_stored_as_small_deltas() {
+        let mut rewind_buffer = RewindBuffer::new(100);
+        let mut snapshot = vec![0xAA; 0x10000];
+
+        for frame in 0..100 {
+            snapshot[0x2400 + frame] = 0xFF;
+            rewind_buffer.push(snapshot.clone());
+        }
+
+        assert!(rewind_buffer.size_in_bytes() < 0x10000 + 100 * 32);
+
+        for frame in (0..100).rev() {
+            let mut expected_snapshot = vec![0xAA; 0x10000];
+            expected_snapshot[0x2400..=0x2400 + frame].fill(0xFF);
+            assert_eq!(rewind_buffer.pop(), Some(expected_snapshot));
+        }
+    }
+}
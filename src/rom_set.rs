@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::State;
+use crate::checksum;
+use crate::zip_archive;
+
+pub struct RomFile {
+    pub file_name: &'static str,
+    pub load_address: u16,
+    pub size: usize,
+    pub crc32: u32,
+    // In lowercase hex
+    pub sha1: &'static str,
+}
+
+// The ROM chips a machine's program is split across, as they are named in MAME
+pub struct RomSet {
+    pub name: &'static str,
+    pub files: &'static [RomFile],
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum RomProblem {
+    Missing,
+    WrongSize { expected: usize, found: usize },
+    WrongChecksum,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum RomSetError {
+    Unreadable(String),
+    // Every problem with the set, so they can all be fixed at once
+    BadFiles(Vec<(&'static str, RomProblem)>),
+}
+
+impl std::fmt::Display for RomSetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RomSetError::Unreadable(message) => write!(f, "{message}"),
+            RomSetError::BadFiles(bad_files) => {
+                write!(f, "The ROM set has bad or missing files:")?;
+                for (file_name, problem) in bad_files {
+                    match problem {
+                        RomProblem::Missing => write!(f, "\n  {file_name}: missing")?,
+                        RomProblem::WrongSize { expected, found } => write!(
+                            f,
+                            "\n  {file_name}: expected {expected} bytes, found {found} bytes"
+                        )?,
+                        RomProblem::WrongChecksum => {
+                            write!(f, "\n  {file_name}: bad dump, the checksum does not match")?
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for RomSetError {}
+
+// File names are matched without case, as ROM sets are often found with uppercase names
+pub fn read_rom_files(path: &Path) -> Result<HashMap<String, Vec<u8>>, RomSetError> {
+    let unreadable = |e: &dyn std::fmt::Display| {
+        RomSetError::Unreadable(format!("Could not read {}: {e}", path.display()))
+    };
+
+    let files = if path.is_dir() {
+        let mut files = HashMap::new();
+        for entry in fs::read_dir(path).map_err(|e| unreadable(&e))? {
+            let entry = entry.map_err(|e| unreadable(&e))?;
+            if entry.path().is_file() {
+                let contents = fs::read(entry.path()).map_err(|e| unreadable(&e))?;
+                files.insert(entry.file_name().to_string_lossy().into_owned(), contents);
+            }
+        }
+        files
+    } else {
+        let archive = fs::read(path).map_err(|e| unreadable(&e))?;
+        zip_archive::extract_files(&archive).map_err(|e| unreadable(&e))?
+    };
+
+    Ok(files
+        .into_iter()
+        .map(|(file_name, contents)| (file_name.to_lowercase(), contents))
+        .collect())
+}
+
+// Files are found by name, or by their CRC-32 if they have been renamed
+fn find_file<'a>(rom_file: &RomFile, files: &'a HashMap<String, Vec<u8>>) -> Option<&'a Vec<u8>> {
+    files.get(&rom_file.file_name.to_lowercase()).or_else(|| {
+        files
+            .values()
+            .find(|contents| checksum::crc32(contents) == rom_file.crc32)
+    })
+}
+
+impl RomSet {
+    pub fn verify(&self, files: &HashMap<String, Vec<u8>>) -> Vec<(&'static str, RomProblem)> {
+        self.files
+            .iter()
+            .filter_map(|rom_file| {
+                let problem = match find_file(rom_file, files) {
+                    None => RomProblem::Missing,
+                    Some(contents) if contents.len() != rom_file.size => RomProblem::WrongSize {
+                        expected: rom_file.size,
+                        found: contents.len(),
+                    },
+                    Some(contents)
+                        if checksum::crc32(contents) != rom_file.crc32
+                            || checksum::to_hex_string(&checksum::sha1(contents))
+                                != rom_file.sha1 =>
+                    {
+                        RomProblem::WrongChecksum
+                    }
+                    Some(_) => return None,
+                };
+                Some((rom_file.file_name, problem))
+            })
+            .collect()
+    }
+
+    // Missing files or files of the wrong size stop the set loading. Bad dumps are still loaded, as
+    // they may only differ slightly, and are returned so they can be reported.
+    pub fn load(
+        &self,
+        state: &mut State,
+        files: &HashMap<String, Vec<u8>>,
+    ) -> Result<Vec<&'static str>, RomSetError> {
+        let (bad_dumps, bad_files): (Vec<_>, Vec<_>) = self
+            .verify(files)
+            .into_iter()
+            .partition(|(_, problem)| *problem == RomProblem::WrongChecksum);
+        if !bad_files.is_empty() {
+            return Err(RomSetError::BadFiles(bad_files));
+        }
+
+        for rom_file in self.files {
+            state.load_memory_at(rom_file.load_address, find_file(rom_file, files).unwrap());
+        }
+        Ok(bad_dumps
+            .into_iter()
+            .map(|(file_name, _)| file_name)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The checksums of [0x00; 4] and [0x01; 4]
+    const TEST_ROM_SET: RomSet = RomSet {
+        name: "test",
+        files: &[
+            RomFile {
+                file_name: "test.h",
+                load_address: 0x0000,
+                size: 4,
+                crc32: 0x2144_DF1C,
+                sha1: "9069ca78e7450a285173431b3e52c5c25299e473",
+            },
+            RomFile {
+                file_name: "test.g",
+                load_address: 0x0004,
+                size: 4,
+                crc32: 0xF626_D399,
+                sha1: "a93755f8273b0e8dc4b0ecc158e5853119a24bf0",
+            },
+        ],
+    };
+
+    #[test]
+    fn find_file_finds_files_by_name_then_by_checksum() {
+        let [first_file, second_file] = TEST_ROM_SET.files else {
+            unreachable!()
+        };
+        let files = HashMap::from([
+            ("test.h".to_owned(), vec![0x02; 4]),
+            ("renamed".to_owned(), vec![0x01; 4]),
+        ]);
+
+        assert_eq!(find_file(first_file, &files), Some(&vec![0x02; 4]));
+        assert_eq!(find_file(second_file, &files), Some(&vec![0x01; 4]));
+        assert_eq!(find_file(second_file, &HashMap::new()), None);
+    }
+
+    #[test]
+    fn load_places_each_file_at_its_load_address() {
+        let files = HashMap::from([
+            ("test.h".to_owned(), vec![0x00; 4]),
+            ("renamed.g".to_owned(), vec![0x01; 4]),
+        ]);
+        let mut state = State::default();
+
+        assert_eq!(TEST_ROM_SET.load(&mut state, &files), Ok(Vec::new()));
+
+        assert_eq!(state.memory.read(0x0003), 0x00);
+        assert_eq!(state.memory.read(0x0004), 0x01);
+        assert_eq!(state.memory.read(0x0007), 0x01);
+        assert_eq!(state.memory.read(0x0008), 0x00);
+    }
+
+    #[test]
+    fn load_reports_every_missing_or_wrongly_sized_file_without_loading() {
+        let files = HashMap::from([
+            ("test.h".to_owned(), vec![0x02; 4]),
+            ("test.g".to_owned(), vec![0x01; 3]),
+        ]);
+        assert_eq!(
+            TEST_ROM_SET.verify(&files),
+            vec![
+                ("test.h", RomProblem::WrongChecksum),
+                (
+                    "test.g",
+                    RomProblem::WrongSize {
+                        expected: 4,
+                        found: 3
+                    }
+                )
+            ]
+        );
+
+        let files = HashMap::from([("test.h".to_owned(), vec![0x02; 4])]);
+        let mut state = State::default();
+        let error = TEST_ROM_SET.load(&mut state, &files).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "The ROM set has bad or missing files:\n  test.g: missing"
+        );
+        assert_eq!(state.memory.read(0x0000), 0x00);
+    }
+
+    #[test]
+    fn load_still_loads_bad_dumps_but_reports_them() {
+        let files = HashMap::from([
+            ("test.h".to_owned(), vec![0x02; 4]),
+            ("test.g".to_owned(), vec![0x01; 4]),
+        ]);
+        let mut state = State::default();
+
+        assert_eq!(TEST_ROM_SET.load(&mut state, &files), Ok(vec!["test.h"]));
+        assert_eq!(state.memory.read(0x0000), 0x02);
+    }
+}
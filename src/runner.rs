@@ -1,6 +1,6 @@
 use crate::{
-    Operation, Register, State, arithmetic_instructions, branch_instructions, logical_instructions,
-    stack_instructions, transfer_instructions,
+    CpuError, CpuStop, Operation, Register, State, arithmetic_instructions, branch_instructions,
+    logical_instructions, stack_instructions, transfer_instructions,
 };
 
 pub fn run_operation(
@@ -9,6 +9,17 @@ pub fn run_operation(
     additional_byte_1: Option<u8>,
     additional_byte_2: Option<u8>,
 ) {
+    if let Err(error) = try_run_operation(operation, state, additional_byte_1, additional_byte_2) {
+        panic!("{error}");
+    }
+}
+
+pub fn try_run_operation(
+    operation: &Operation,
+    state: &mut State,
+    additional_byte_1: Option<u8>,
+    additional_byte_2: Option<u8>,
+) -> Result<(), CpuError> {
     let mut is_low_data_required = false;
     let mut is_high_data_required = false;
 
@@ -44,10 +55,10 @@ pub fn run_operation(
         Operation::Lhld => transfer_instructions::lhld_instruction(state, low_data(), high_data()),
         Operation::Shld => transfer_instructions::shld_instruction(state, low_data(), high_data()),
         Operation::Ldax(register_pair) => {
-            transfer_instructions::ldax_instruction(state, *register_pair);
+            transfer_instructions::ldax_instruction(state, *register_pair)?;
         }
         Operation::Stax(register_pair) => {
-            transfer_instructions::stax_instruction(state, *register_pair);
+            transfer_instructions::stax_instruction(state, *register_pair)?;
         }
         Operation::Xchg => transfer_instructions::xchg_instruction(state),
         Operation::Add(register) => arithmetic_instructions::add_instruction(state, *register),
@@ -97,30 +108,30 @@ pub fn run_operation(
         Operation::Stc => logical_instructions::stc_instruction(state),
         Operation::Jmp => branch_instructions::jmp_instruction(state, low_data(), high_data()),
         Operation::Jcond(condition) => {
-            branch_instructions::jcond_instruction(state, low_data(), high_data(), *condition);
+            branch_instructions::jcond_instruction(state, low_data(), high_data(), *condition)?;
         }
         Operation::Call => branch_instructions::call_instruction(state, low_data(), high_data()),
         Operation::Ccond(condition) => {
-            branch_instructions::ccond_instruction(state, low_data(), high_data(), *condition);
+            branch_instructions::ccond_instruction(state, low_data(), high_data(), *condition)?;
         }
         Operation::Ret => branch_instructions::ret_instruction(state),
-        Operation::Rcond(condition) => branch_instructions::rcond_instruction(state, *condition),
-        Operation::Rst(reset_index) => branch_instructions::rst_instruction(state, *reset_index),
+        Operation::Rcond(condition) => branch_instructions::rcond_instruction(state, *condition)?,
+        Operation::Rst(reset_index) => branch_instructions::rst_instruction(state, *reset_index)?,
         Operation::Pchl => branch_instructions::pchl_instruction(state),
         Operation::Push(register_pair) => {
-            stack_instructions::push_instruction(state, *register_pair);
+            stack_instructions::push_instruction(state, *register_pair)?;
+        }
+        Operation::Pop(register_pair) => {
+            stack_instructions::pop_instruction(state, *register_pair)?
         }
-        Operation::Pop(register_pair) => stack_instructions::pop_instruction(state, *register_pair),
         Operation::PushPsw => stack_instructions::push_psw_instruction(state),
         Operation::PopPsw => stack_instructions::pop_psw_instruction(state),
         Operation::Xthl => stack_instructions::xthl_instruction(state),
         Operation::Sphl => stack_instructions::sphl_instruction(state),
         Operation::In => {
-            state.registers[Register::A] = state.ports.read_in_port(low_data());
+            state.registers[Register::A] = state.read_in_port(low_data())?;
         }
-        Operation::Out => state
-            .ports
-            .write_out_port(low_data(), state.registers[Register::A]),
+        Operation::Out => state.write_out_port(low_data(), state.registers[Register::A])?,
         Operation::Ei => stack_instructions::ei_instruction(state),
         Operation::Di => stack_instructions::di_instruction(state),
         Operation::Hlt => stack_instructions::hlt_instruction(state),
@@ -134,6 +145,8 @@ pub fn run_operation(
     if !is_low_data_required && additional_byte_1.is_some() {
         panic!("Expected byte 1 to not be present but it was");
     }
+
+    Ok(())
 }
 
 pub fn run_next_operation(state: &mut State) {
@@ -141,3 +154,16 @@ pub fn run_next_operation(state: &mut State) {
     let operation = crate::disassembler::disassemble_op_code(memory_value);
     state.run_operation(&operation);
 }
+
+// Stops after an instruction that hits a watchpoint or port breakpoint
+pub fn try_run_next_operation(state: &mut State) -> Result<(), CpuStop> {
+    let memory_value = state.memory_value_at_pc();
+    let operation = crate::disassembler::disassemble_op_code(memory_value);
+    state
+        .try_run_operation(&operation)
+        .map_err(CpuStop::Fault)?;
+    match state.watchpoints.take_triggered_event() {
+        Some(event) => Err(CpuStop::Break(event)),
+        None => Ok(()),
+    }
+}
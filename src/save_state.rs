@@ -0,0 +1,424 @@
+use crate::{MEMORY_SIZE, Register, State};
+
+const MAGIC: &[u8; 8] = b"EMU8080S";
+pub const SAVE_STATE_VERSION: u16 = 2;
+
+const REGISTER_ORDER: [Register; 7] = [
+    Register::A,
+    Register::B,
+    Register::C,
+    Register::D,
+    Register::E,
+    Register::H,
+    Register::L,
+];
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum SaveStateError {
+    InvalidHeader,
+    UnsupportedVersion(u16),
+    MachineMismatch { expected: String, found: String },
+    Truncated,
+    TrailingData,
+    InvalidValue(&'static str),
+}
+
+impl std::fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveStateError::InvalidHeader => write!(f, "Not a save state file"),
+            SaveStateError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported save state version {version}")
+            }
+            SaveStateError::MachineMismatch { expected, found } => write!(
+                f,
+                "Save state is for the machine {found}, but the running machine is {expected}"
+            ),
+            SaveStateError::Truncated => write!(f, "Save state ended unexpectedly"),
+            SaveStateError::TrailingData => write!(f, "Save state has unexpected trailing data"),
+            SaveStateError::InvalidValue(name) => {
+                write!(f, "Save state has an invalid value for {name}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+#[derive(Default)]
+pub struct SaveStateWriter {
+    bytes: Vec<u8>,
+}
+
+impl SaveStateWriter {
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(u8::from(value));
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Length-prefixed, so that the reader can check a section was fully consumed
+    pub fn write_section(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub struct SaveStateReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> SaveStateReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        SaveStateReader { bytes }
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], SaveStateError> {
+        let (array, rest) = self
+            .bytes
+            .split_first_chunk::<N>()
+            .ok_or(SaveStateError::Truncated)?;
+        self.bytes = rest;
+        Ok(*array)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, SaveStateError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveStateError::InvalidValue("a flag")),
+        }
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_section(&mut self) -> Result<&'a [u8], SaveStateError> {
+        let length = self.read_u32()? as usize;
+        if length > self.bytes.len() {
+            return Err(SaveStateError::Truncated);
+        }
+        let (section, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(section)
+    }
+
+    pub fn finish(&self) -> Result<(), SaveStateError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(SaveStateError::TrailingData)
+        }
+    }
+}
+
+// The frontend data is opaque to the core, for anything else the host needs to resume at the same point.
+// The position within the frame doesn't need saving, as the scheduler works it out from the state count.
+pub fn save(state: &State, machine_name: &str, frontend_data: &[u8]) -> Vec<u8> {
+    let mut writer = SaveStateWriter::default();
+    writer.bytes.extend_from_slice(MAGIC);
+    writer.write_u16(SAVE_STATE_VERSION);
+    writer.write_section(machine_name.as_bytes());
+
+    for register in REGISTER_ORDER {
+        writer.write_u8(state.registers[register]);
+    }
+    writer.write_u8(state.condition_flag_byte());
+    writer.write_u16(state.program_counter);
+    writer.write_u16(state.stack_pointer);
+    writer.write_bool(state.are_interrupts_enabled);
+    writer.write_bool(state.is_halted);
+    writer.write_u64(state.cpu_total_state_count as u64);
+
+    let memory: Vec<u8> = (0..MEMORY_SIZE)
+        .map(|address| state.memory.read(address as u16))
+        .collect();
+    writer.write_section(&memory);
+
+    let mut ports_writer = SaveStateWriter::default();
+    state.ports.save_state(&mut ports_writer);
+    writer.write_section(&ports_writer.into_bytes());
+
+    writer.write_section(frontend_data);
+    writer.into_bytes()
+}
+
+// Returns the frontend data. The state, ports included, is only changed once the whole file has been validated.
+pub fn load(
+    state: &mut State,
+    machine_name: &str,
+    bytes: &[u8],
+) -> Result<Vec<u8>, SaveStateError> {
+    let mut reader = SaveStateReader::new(bytes);
+
+    if reader.read_array::<8>().ok().as_ref() != Some(MAGIC) {
+        return Err(SaveStateError::InvalidHeader);
+    }
+
+    let version = reader.read_u16()?;
+    if version != SAVE_STATE_VERSION {
+        return Err(SaveStateError::UnsupportedVersion(version));
+    }
+
+    let saved_machine_name = String::from_utf8_lossy(reader.read_section()?).into_owned();
+    if saved_machine_name != machine_name {
+        return Err(SaveStateError::MachineMismatch {
+            expected: machine_name.to_owned(),
+            found: saved_machine_name,
+        });
+    }
+
+    let mut register_values = [0; REGISTER_ORDER.len()];
+    for register_value in &mut register_values {
+        *register_value = reader.read_u8()?;
+    }
+    let condition_flag_byte = reader.read_u8()?;
+    let program_counter = reader.read_u16()?;
+    let stack_pointer = reader.read_u16()?;
+    let are_interrupts_enabled = reader.read_bool()?;
+    let is_halted = reader.read_bool()?;
+    let cpu_total_state_count = usize::try_from(reader.read_u64()?)
+        .map_err(|_| SaveStateError::InvalidValue("the cycle count"))?;
+
+    let memory = reader.read_section()?;
+    if memory.len() != MEMORY_SIZE {
+        return Err(SaveStateError::InvalidValue("memory"));
+    }
+
+    let mut ports_reader = SaveStateReader::new(reader.read_section()?);
+    let frontend_data = reader.read_section()?.to_vec();
+    reader.finish()?;
+
+    // Ports load their fields one at a time, so they are put back if their section turns out to be bad
+    let mut previous_ports_writer = SaveStateWriter::default();
+    state.ports.save_state(&mut previous_ports_writer);
+    let ports_result = state
+        .ports
+        .load_state(&mut ports_reader)
+        .and_then(|()| ports_reader.finish());
+    if let Err(e) = ports_result {
+        let previous_ports = previous_ports_writer.into_bytes();
+        state
+            .ports
+            .load_state(&mut SaveStateReader::new(&previous_ports))
+            .expect("Ports should load the state they saved");
+        // Loading and putting back the ports leaves the sound as it was
+        state.ports.take_audio_events();
+        return Err(e);
+    }
+
+    for (register, value) in REGISTER_ORDER.iter().zip(register_values) {
+        state.registers[*register] = value;
+    }
+    state.set_condition_flags_from_byte(condition_flag_byte);
+    state.program_counter = program_counter;
+    state.stack_pointer = stack_pointer;
+    state.are_interrupts_enabled = are_interrupts_enabled;
+    state.is_halted = is_halted;
+    state.cpu_total_state_count = cpu_total_state_count;
+    for (address, value) in memory.iter().enumerate() {
+        state.memory.poke(address as u16, *value);
+    }
+    // Sounds the ports started or stopped to match the loaded state
+    state.collect_port_audio_events();
+
+    Ok(frontend_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConditionFlag, CpuError, Operation, Ports, StateBuilder};
+    use maplit::hashmap;
+
+    #[derive(Default)]
+    struct LatchPorts {
+        latch: u8,
+    }
+
+    impl Ports for LatchPorts {
+        fn read_in_port(&self, _port_number: u8) -> Result<u8, CpuError> {
+            Ok(self.latch)
+        }
+        fn write_out_port(&mut self, _port_number: u8, value: u8) -> Result<(), CpuError> {
+            self.latch = value;
+            Ok(())
+        }
+        fn in_port_static_value(&self, _port_number: u8) -> Option<u8> {
+            None
+        }
+        fn set_in_port_static_value(&mut self, _port_number: u8, _value: u8) {}
+        fn save_state(&self, writer: &mut SaveStateWriter) {
+            writer.write_u8(self.latch);
+        }
+        fn load_state(&mut self, reader: &mut SaveStateReader) -> Result<(), SaveStateError> {
+            self.latch = reader.read_u8()?;
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct PairPorts {
+        values: [u8; 2],
+    }
+
+    impl Ports for PairPorts {
+        fn read_in_port(&self, port_number: u8) -> Result<u8, CpuError> {
+            Ok(self.values[usize::from(port_number) % 2])
+        }
+        fn write_out_port(&mut self, port_number: u8, value: u8) -> Result<(), CpuError> {
+            self.values[usize::from(port_number) % 2] = value;
+            Ok(())
+        }
+        fn in_port_static_value(&self, _port_number: u8) -> Option<u8> {
+            None
+        }
+        fn set_in_port_static_value(&mut self, _port_number: u8, _value: u8) {}
+        fn save_state(&self, writer: &mut SaveStateWriter) {
+            writer.write_u8(self.values[0]);
+            writer.write_u8(self.values[1]);
+        }
+        fn load_state(&mut self, reader: &mut SaveStateReader) -> Result<(), SaveStateError> {
+            self.values[0] = reader.read_u8()?;
+            self.values[1] = reader.read_u8()?;
+            Ok(())
+        }
+    }
+
+    fn example_state() -> State {
+        let mut state = StateBuilder::default()
+            .register_values(hashmap! { Register::A => 0x12, Register::L => 0x34 })
+            .condition_flag_values(
+                hashmap! { ConditionFlag::Carry => true, ConditionFlag::Zero => true },
+            )
+            .program_counter(0x1234)
+            .stack_pointer(0x2400)
+            .memory_values(hashmap! { 0x1235 => 0x56, 0xFFFF => 0x78 })
+            .interrupts_enabled(true)
+            .build();
+        state.ports = Box::<LatchPorts>::default();
+        state.run_operation(&Operation::Out);
+        state
+    }
+
+    #[test]
+    fn load_restores_everything_that_was_saved() {
+        let saved_state = example_state();
+        let bytes = save(&saved_state, "Test", &[0x60, 0x00]);
+
+        let mut state = StateBuilder::default().build();
+        state.ports = Box::<LatchPorts>::default();
+        let frontend_data = load(&mut state, "Test", &bytes).unwrap();
+
+        assert_eq!(frontend_data, vec![0x60, 0x00]);
+        crate::base_test_functions::assert_state_is_as_expected(&state, &saved_state);
+        assert_eq!(
+            state.cpu_total_state_count(),
+            saved_state.cpu_total_state_count()
+        );
+        assert_eq!(state.ports.read_in_port(0).unwrap(), 0x12);
+    }
+
+    #[test]
+    fn load_rejects_a_save_state_for_another_machine_without_changing_the_state() {
+        let bytes = save(&example_state(), "Space Invaders", &[]);
+        let mut state = State::default();
+
+        assert_eq!(
+            load(&mut state, "Boot Hill", &bytes),
+            Err(SaveStateError::MachineMismatch {
+                expected: "Boot Hill".to_owned(),
+                found: "Space Invaders".to_owned(),
+            })
+        );
+        assert_eq!(state.program_counter, 0x0000);
+    }
+
+    #[test]
+    fn load_rejects_invalid_headers_versions_and_truncated_files() {
+        let mut bytes = save(&example_state(), "Test", &[]);
+        let mut state = StateBuilder::default().build();
+        state.ports = Box::<LatchPorts>::default();
+
+        assert_eq!(
+            load(&mut state, "Test", b"NOT A SAVE STATE"),
+            Err(SaveStateError::InvalidHeader)
+        );
+        assert_eq!(
+            load(&mut state, "Test", &bytes[..bytes.len() - 1]),
+            Err(SaveStateError::Truncated)
+        );
+
+        bytes[MAGIC.len()] = 0xFF;
+        assert_eq!(
+            load(&mut state, "Test", &bytes),
+            Err(SaveStateError::UnsupportedVersion(0x00FF))
+        );
+    }
+
+    #[test]
+    fn load_rejects_port_state_that_the_ports_do_not_consume() {
+        let bytes = save(&example_state(), "Test", &[]);
+        let mut state = State::default();
+
+        assert_eq!(
+            load(&mut state, "Test", &bytes),
+            Err(SaveStateError::TrailingData)
+        );
+    }
+
+    #[test]
+    fn ports_are_unchanged_when_their_section_is_truncated_or_too_long() {
+        let mut state = StateBuilder::default().build();
+        state.ports = Box::new(PairPorts {
+            values: [0x56, 0x78],
+        });
+        assert_eq!(
+            load(&mut state, "Test", &save(&example_state(), "Test", &[])),
+            Err(SaveStateError::Truncated)
+        );
+        assert_eq!(state.ports.read_in_port(0).unwrap(), 0x56);
+        assert_eq!(state.ports.read_in_port(1).unwrap(), 0x78);
+        assert_eq!(state.program_counter, 0x0000);
+
+        let bytes = save(&state, "Test", &[]);
+        let mut state = StateBuilder::default().build();
+        state.ports = Box::new(LatchPorts { latch: 0x9A });
+        assert_eq!(
+            load(&mut state, "Test", &bytes),
+            Err(SaveStateError::TrailingData)
+        );
+        assert_eq!(state.ports.read_in_port(0).unwrap(), 0x9A);
+    }
+}
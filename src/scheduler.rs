@@ -0,0 +1,211 @@
+use log::debug;
+
+use crate::{CpuError, CpuStop, State, branch_instructions, runner};
+
+pub const CPU_CLOCK_SPEED: usize = 2_000_000;
+pub const FRAME_RATE: usize = 60;
+pub const NUM_SCAN_LINES: usize = 262;
+
+// From http://computerarcheology.com/Arcade/SpaceInvaders/Hardware.html
+// RST 1 when the beam reaches the middle of the screen, RST 2 when it starts the vertical blank
+const VIDEO_INTERRUPTS: [(usize, u8); 2] = [(96, 1), (224, 2)];
+
+// Frames are counted from the first cycle, so the position within a frame can always be worked out
+// from the state count alone, including after loading a save state.
+pub fn frame_start_state_count(frame_number: usize) -> usize {
+    frame_number * CPU_CLOCK_SPEED / FRAME_RATE
+}
+
+pub fn scan_line_state_count(frame_number: usize, scan_line: usize) -> usize {
+    let frame_start = frame_start_state_count(frame_number);
+    let frame_length = frame_start_state_count(frame_number + 1) - frame_start;
+    frame_start + scan_line * frame_length / NUM_SCAN_LINES
+}
+
+pub fn frame_number(state_count: usize) -> usize {
+    let mut frame_number = state_count * FRAME_RATE / CPU_CLOCK_SPEED;
+    while frame_start_state_count(frame_number + 1) <= state_count {
+        frame_number += 1;
+    }
+    frame_number
+}
+
+pub fn raise_interrupt(state: &mut State, reset_index: u8) -> Result<(), CpuError> {
+    if state.are_interrupts_enabled {
+        state.is_halted = false;
+        debug!("-- Raised interrupt with reset index of {} --", reset_index);
+        branch_instructions::rst_instruction(state, reset_index)?;
+    }
+    Ok(())
+}
+
+// The state count and reset index of the first video interrupt after the given state count
+pub fn next_video_interrupt(state_count: usize) -> (usize, u8) {
+    let frame_number = frame_number(state_count);
+
+    [frame_number, frame_number + 1]
+        .into_iter()
+        .flat_map(|frame_number| {
+            VIDEO_INTERRUPTS.map(|(scan_line, reset_index)| {
+                (scan_line_state_count(frame_number, scan_line), reset_index)
+            })
+        })
+        .find(|(interrupt_state_count, _)| *interrupt_state_count > state_count)
+        .expect("The next frame should have video interrupts")
+}
+
+// Runs until the end of the current frame, raising each video interrupt at the first instruction
+// boundary at or after its scan line. After a break, calling it again finishes the same frame.
+pub fn run_frame(state: &mut State) -> Result<(), CpuStop> {
+    let frame_number = frame_number(state.cpu_total_state_count());
+
+    for (scan_line, reset_index) in VIDEO_INTERRUPTS {
+        let interrupt_state_count = scan_line_state_count(frame_number, scan_line);
+        if state.cpu_total_state_count() < interrupt_state_count {
+            let result = run_until(state, interrupt_state_count);
+            // The instruction that hit a break may have reached the interrupt, which would
+            // otherwise be missed when the frame carries on
+            if !matches!(result, Err(CpuStop::Fault(_)))
+                && state.cpu_total_state_count() >= interrupt_state_count
+            {
+                raise_interrupt(state, reset_index)
+                    .expect("Video interrupts should have valid reset indexes");
+            }
+            result?;
+        }
+    }
+
+    run_until(state, frame_start_state_count(frame_number + 1))
+}
+
+fn run_until(state: &mut State, state_count: usize) -> Result<(), CpuStop> {
+    while state.cpu_total_state_count() < state_count {
+        if state.is_halted {
+            state.skip_to_state_count(state_count);
+        } else {
+            runner::try_run_next_operation(state)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watchpoints::{Access, BreakEvent, BreakTrigger, WatchKind};
+    use crate::{Register, assembler};
+
+    fn state_from_source(source: &str) -> State {
+        let mut state = State::default();
+        state.load_memory(&assembler::assemble(source).unwrap());
+        state
+    }
+
+    #[test]
+    fn frames_start_at_exact_state_counts() {
+        assert_eq!(frame_start_state_count(1), 33_333);
+        assert_eq!(frame_start_state_count(3), 100_000);
+        assert_eq!(frame_start_state_count(60), CPU_CLOCK_SPEED);
+        assert_eq!(frame_number(33_332), 0);
+        assert_eq!(frame_number(33_333), 1);
+        assert_eq!(frame_number(100_000), 3);
+        assert_eq!(scan_line_state_count(0, 96), 12_213);
+    }
+
+    #[test]
+    fn next_video_interrupt_is_the_first_after_the_state_count() {
+        let vblank_state_count = scan_line_state_count(0, 224);
+        assert_eq!(next_video_interrupt(0), (12_213, 1));
+        assert_eq!(next_video_interrupt(12_213), (vblank_state_count, 2));
+        assert_eq!(
+            next_video_interrupt(vblank_state_count),
+            (scan_line_state_count(1, 96), 1)
+        );
+    }
+
+    #[test]
+    fn run_frame_raises_both_video_interrupts_and_skips_halted_cycles() {
+        let mut state = state_from_source(
+            "
+            LXI SP,2400h
+            EI
+            HLT
+            ORG 08h
+            INR B
+            HLT
+            ORG 10h
+            INR C
+            HLT",
+        );
+
+        run_frame(&mut state).unwrap();
+
+        assert_eq!(state.registers[Register::B], 1);
+        assert_eq!(state.registers[Register::C], 1);
+        assert_eq!(state.stack_pointer, 0x23FC);
+        assert_eq!(state.cpu_total_state_count(), 33_333);
+
+        run_frame(&mut state).unwrap();
+
+        assert_eq!(state.registers[Register::B], 2);
+        assert_eq!(state.registers[Register::C], 2);
+        assert_eq!(state.cpu_total_state_count(), 66_666);
+    }
+
+    #[test]
+    fn run_frame_does_not_raise_interrupts_while_they_are_disabled() {
+        let mut state = state_from_source(
+            "
+            LOOP: INR B
+            JMP LOOP",
+        );
+
+        run_frame(&mut state).unwrap();
+
+        assert_eq!(state.stack_pointer, 0x0000);
+        assert!(state.cpu_total_state_count() >= 33_333);
+        assert!(state.cpu_total_state_count() < 33_333 + 10);
+    }
+
+    #[test]
+    fn run_frame_stops_at_a_watchpoint_and_can_finish_the_frame_after() {
+        let mut state = state_from_source(
+            "
+            LXI SP,2400h
+            JMP 20h
+            ORG 08h
+            INR B
+            HLT
+            ORG 10h
+            INR C
+            HLT
+            ORG 20h
+            EI
+            MVI A,12h
+            STA 2000h
+            HLT",
+        );
+        state
+            .watchpoints
+            .add_watchpoint(0x2000..=0x2000, WatchKind::Write);
+
+        assert_eq!(
+            run_frame(&mut state),
+            Err(CpuStop::Break(BreakEvent {
+                program_counter: 0x0023,
+                trigger: BreakTrigger::Memory {
+                    access: Access::Write,
+                    address: 0x2000
+                },
+                value: 0x12
+            }))
+        );
+        assert_eq!(state.program_counter, 0x0026);
+
+        run_frame(&mut state).unwrap();
+        assert_eq!(state.registers[Register::B], 1);
+        assert_eq!(state.registers[Register::C], 1);
+        assert_eq!(state.cpu_total_state_count(), 33_333);
+    }
+}
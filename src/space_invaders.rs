@@ -0,0 +1,543 @@
+use std::collections::HashMap;
+
+use log::debug;
+use maplit::hashmap;
+
+use crate::machine::{
+    AudioEvent, Color, DipSwitch, DipSwitches, Input, Machine, OverlayArea, SoundKind, SoundName,
+    set_in_port_from_flags, shift_new_value_into_data, shift_value,
+};
+use crate::memory::MidwayMemory;
+use crate::rom_set::{RomFile, RomSet};
+use crate::save_state::{SaveStateError, SaveStateReader, SaveStateWriter};
+use crate::{CpuError, Ports, State, bit_operations};
+
+// From http://computerarcheology.com/Arcade/SpaceInvaders/RAMUse.html
+// Set while a game is being played, rather than the demo
+const GAME_MODE_ADDRESS: u16 = 0x20EF;
+// The high byte of the address of the current player's data, 0x21 for player 1 and 0x22 for player 2
+const PLAYER_DATA_MSB_ADDRESS: u16 = 0x2067;
+const PLAYER_2_DATA_MSB: u8 = 0x22;
+
+// From http://computerarcheology.com/Arcade/SpaceInvaders/Hardware.html
+const DIP_SWITCHES: [DipSwitch; 3] = [
+    DipSwitch {
+        name: "ships",
+        description: "Number of ships at the start of a game",
+        mask: 0b0000_0011,
+        values: &[("3", 0b00), ("4", 0b01), ("5", 0b10), ("6", 0b11)],
+        default_value: "3",
+    },
+    DipSwitch {
+        name: "extra_ship",
+        description: "Score needed for an extra ship",
+        mask: 0b0000_1000,
+        values: &[("1500", 0b0000_0000), ("1000", 0b0000_1000)],
+        default_value: "1500",
+    },
+    DipSwitch {
+        name: "coin_info",
+        description: "Whether the coin information is shown in the demo",
+        mask: 0b1000_0000,
+        values: &[("on", 0b0000_0000), ("off", 0b1000_0000)],
+        default_value: "on",
+    },
+];
+
+// From https://tcrf.net/File:SpaceInvadersArcColorUseTV.png
+pub const OVERLAY: [OverlayArea; 3] = [
+    OverlayArea {
+        x: 0..224,
+        y: 32..64,
+        color: Color::RED,
+    },
+    OverlayArea {
+        x: 0..224,
+        y: 178..240,
+        color: Color::GREEN,
+    },
+    OverlayArea {
+        x: 24..136,
+        y: 240..256,
+        color: Color::GREEN,
+    },
+];
+
+// From MAME
+pub const ROM_SET: RomSet = RomSet {
+    name: "invaders",
+    files: &[
+        RomFile {
+            file_name: "invaders.h",
+            load_address: 0x0000,
+            size: 0x0800,
+            crc32: 0x734F_5AD8,
+            sha1: "ff6200af4c9110d8181249cbcef1a8a40fa40b7f",
+        },
+        RomFile {
+            file_name: "invaders.g",
+            load_address: 0x0800,
+            size: 0x0800,
+            crc32: 0x6BFA_CA4A,
+            sha1: "16f48649b531bdef8c2d1446c429b5f414524350",
+        },
+        RomFile {
+            file_name: "invaders.f",
+            load_address: 0x1000,
+            size: 0x0800,
+            crc32: 0x0CCE_AD96,
+            sha1: "537aef03468f63c5b9e11dd61e253f7ae17d9743",
+        },
+        RomFile {
+            file_name: "invaders.e",
+            load_address: 0x1800,
+            size: 0x0800,
+            crc32: 0x14E5_38B0,
+            sha1: "1d6ca0c99f9df71e2990b610deb9d7da0125e2d8",
+        },
+    ],
+};
+
+pub struct SpaceInvadersMachine {
+    state: State,
+    inputs: SpaceInvadersInputs,
+    dip_switches: DipSwitches,
+}
+
+impl Default for SpaceInvadersMachine {
+    fn default() -> Self {
+        SpaceInvadersMachine {
+            state: State {
+                memory: Box::<MidwayMemory>::default(),
+                ports: Box::<SpaceInvadersPorts>::default(),
+                ..State::default()
+            },
+            inputs: SpaceInvadersInputs::default(),
+            dip_switches: DipSwitches::new(&DIP_SWITCHES),
+        }
+    }
+}
+
+impl Machine for SpaceInvadersMachine {
+    fn state(&self) -> &State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+
+    fn input_actions(&self) -> &'static [Input] {
+        &[
+            Input::Coin,
+            Input::P1Start,
+            Input::P2Start,
+            Input::P1Fire,
+            Input::P1Left,
+            Input::P1Right,
+            Input::P2Fire,
+            Input::P2Left,
+            Input::P2Right,
+            Input::Tilt,
+        ]
+    }
+
+    fn set_input(&mut self, input: Input, is_pressed: bool) {
+        match input {
+            Input::Coin => self.inputs.credit = is_pressed,
+            Input::Tilt => self.inputs.tilt = is_pressed,
+            Input::P1Start => self.inputs.p1_start = is_pressed,
+            Input::P2Start => self.inputs.p2_start = is_pressed,
+            Input::P1Fire => self.inputs.p1_shoot = is_pressed,
+            Input::P1Left => self.inputs.p1_left = is_pressed,
+            Input::P1Right => self.inputs.p1_right = is_pressed,
+            Input::P2Fire => self.inputs.p2_shoot = is_pressed,
+            Input::P2Left => self.inputs.p2_left = is_pressed,
+            Input::P2Right => self.inputs.p2_right = is_pressed,
+            _ => {}
+        }
+    }
+
+    fn set_ports_from_inputs(&mut self) {
+        set_in_port_from_flags(
+            &mut self.state.ports,
+            0,
+            hashmap! {
+                4 => self.inputs.p1_shoot || self.inputs.p2_shoot,
+                5 => self.inputs.p1_left || self.inputs.p2_left,
+                6 => self.inputs.p1_right || self.inputs.p2_right,
+            },
+        );
+
+        set_in_port_from_flags(
+            &mut self.state.ports,
+            1,
+            hashmap! {
+                0 => !self.inputs.credit,
+                1 => self.inputs.p2_start,
+                2 => self.inputs.p1_start,
+                4 => self.inputs.p1_shoot,
+                5 => self.inputs.p1_left,
+                6 => self.inputs.p1_right,
+            },
+        );
+
+        set_in_port_from_flags(
+            &mut self.state.ports,
+            2,
+            hashmap! {
+                2 => self.inputs.tilt,
+                4 => self.inputs.p2_shoot,
+                5 => self.inputs.p2_left,
+                6 => self.inputs.p2_right,
+                0 => self.dip_switches.is_bit_set(0),
+                1 => self.dip_switches.is_bit_set(1),
+                3 => self.dip_switches.is_bit_set(3),
+                7 => self.dip_switches.is_bit_set(7),
+            },
+        );
+    }
+
+    fn overlay(&self) -> &'static [OverlayArea] {
+        &OVERLAY
+    }
+
+    fn orientation(&self) -> u32 {
+        270
+    }
+
+    fn dip_switches(&self) -> Option<&DipSwitches> {
+        Some(&self.dip_switches)
+    }
+
+    fn dip_switches_mut(&mut self) -> Option<&mut DipSwitches> {
+        Some(&mut self.dip_switches)
+    }
+
+    fn active_player(&self) -> Option<u8> {
+        let memory = &self.state.memory;
+        if memory.read(GAME_MODE_ADDRESS) == 0 {
+            None
+        } else if memory.read(PLAYER_DATA_MSB_ADDRESS) == PLAYER_2_DATA_MSB {
+            Some(2)
+        } else {
+            Some(1)
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Space Invaders"
+    }
+}
+
+struct SpaceInvadersPorts {
+    shift_data: u16,
+    shift_amount: u8,
+    in_port_0: u8,
+    in_port_1: u8,
+    in_port_2: u8,
+    out_port_3: u8,
+    out_port_5: u8,
+    watchdog: u8,
+    audio_events: Vec<AudioEvent>,
+}
+
+impl Default for SpaceInvadersPorts {
+    fn default() -> Self {
+        SpaceInvadersPorts {
+            shift_data: 0b0000_0000_0000_0000,
+            shift_amount: 0b0000_0000,
+            in_port_0: 0b0000_1110,
+            in_port_1: 0b0000_1000,
+            in_port_2: 0b0000_0000,
+            out_port_3: 0b0000_0000,
+            out_port_5: 0b0000_0000,
+            watchdog: 0b0000_0000,
+            audio_events: Vec::new(),
+        }
+    }
+}
+
+impl Ports for SpaceInvadersPorts {
+    fn read_in_port(&self, port_number: u8) -> Result<u8, CpuError> {
+        match port_number {
+            #[allow(clippy::manual_range_patterns)]
+            0 | 1 | 2 => Ok(self.in_port_static_value(port_number).unwrap()),
+            3 => Ok(shift_value(self.shift_data, self.shift_amount)),
+            _ => Err(CpuError::InvalidInputPort(port_number)),
+        }
+    }
+
+    fn write_out_port(&mut self, port_number: u8, value: u8) -> Result<(), CpuError> {
+        match port_number {
+            3 => {
+                self.update_sounds(self.out_port_3, value, port_3_sounds());
+                self.out_port_3 = value;
+            }
+            5 => {
+                self.update_sounds(self.out_port_5, value, port_5_sounds());
+                self.out_port_5 = value;
+            }
+            2 => self.shift_amount = value & 0b0000_0111,
+            4 => self.shift_data = shift_new_value_into_data(self.shift_data, value),
+            6 => {
+                self.watchdog = value;
+                debug!("Watchdog: {}", self.watchdog);
+            }
+            _ => return Err(CpuError::InvalidOutputPort(port_number)),
+        };
+
+        Ok(())
+    }
+
+    fn in_port_static_value(&self, port_number: u8) -> Option<u8> {
+        match port_number {
+            0 => Some(self.in_port_0),
+            1 => Some(self.in_port_1),
+            2 => Some(self.in_port_2),
+            _ => None,
+        }
+    }
+
+    fn set_in_port_static_value(&mut self, port_number: u8, value: u8) {
+        match port_number {
+            0 => self.in_port_0 = value,
+            1 => self.in_port_1 = value,
+            2 => self.in_port_2 = value,
+            _ => {}
+        }
+    }
+
+    fn save_state(&self, writer: &mut SaveStateWriter) {
+        writer.write_u16(self.shift_data);
+        writer.write_u8(self.shift_amount);
+        writer.write_u8(self.in_port_0);
+        writer.write_u8(self.in_port_1);
+        writer.write_u8(self.in_port_2);
+        writer.write_u8(self.out_port_3);
+        writer.write_u8(self.out_port_5);
+        writer.write_u8(self.watchdog);
+    }
+
+    fn load_state(&mut self, reader: &mut SaveStateReader) -> Result<(), SaveStateError> {
+        let previous_out_port_3 = self.out_port_3;
+        let previous_out_port_5 = self.out_port_5;
+        self.shift_data = reader.read_u16()?;
+        self.shift_amount = reader.read_u8()?;
+        self.in_port_0 = reader.read_u8()?;
+        self.in_port_1 = reader.read_u8()?;
+        self.in_port_2 = reader.read_u8()?;
+        self.out_port_3 = reader.read_u8()?;
+        self.out_port_5 = reader.read_u8()?;
+        self.watchdog = reader.read_u8()?;
+
+        // A looped sound keeps playing until told otherwise, so it is started or stopped to match
+        // the loaded state. One-shot sounds are left to finish.
+        let looped_sounds = |sounds: HashMap<u8, &'static SoundName>| {
+            sounds
+                .into_iter()
+                .filter(|(_, sound_name)| sound_name.kind() == SoundKind::Looped)
+                .collect()
+        };
+        self.update_sounds(
+            previous_out_port_3,
+            self.out_port_3,
+            looped_sounds(port_3_sounds()),
+        );
+        self.update_sounds(
+            previous_out_port_5,
+            self.out_port_5,
+            looped_sounds(port_5_sounds()),
+        );
+        Ok(())
+    }
+
+    fn take_audio_events(&mut self) -> Vec<AudioEvent> {
+        std::mem::take(&mut self.audio_events)
+    }
+}
+
+fn port_3_sounds() -> HashMap<u8, &'static SoundName> {
+    hashmap! {
+        0 => &SoundName::UfoFly,
+        1 => &SoundName::Shoot,
+        2 => &SoundName::PlayerKilled,
+        3 => &SoundName::InvaderKilled,
+    }
+}
+
+fn port_5_sounds() -> HashMap<u8, &'static SoundName> {
+    hashmap! {
+        0 => &SoundName::InvaderMovement1,
+        1 => &SoundName::InvaderMovement2,
+        2 => &SoundName::InvaderMovement3,
+        3 => &SoundName::InvaderMovement4,
+        4 => &SoundName::UfoKilled,
+    }
+}
+
+impl SpaceInvadersPorts {
+    // Sounds start when their bit is set, and looped sounds stop again when it is cleared
+    fn update_sounds(
+        &mut self,
+        port_value: u8,
+        new_value: u8,
+        bit_index_to_sound_name_map: HashMap<u8, &SoundName>,
+    ) {
+        for (bit_index, sound_name) in bit_index_to_sound_name_map {
+            let was_set = bit_operations::is_bit_set(port_value, bit_index);
+            let is_set = bit_operations::is_bit_set(new_value, bit_index);
+            if is_set && !was_set {
+                self.audio_events.push(AudioEvent::Play(*sound_name));
+            } else if was_set && !is_set && sound_name.kind() == SoundKind::Looped {
+                self.audio_events.push(AudioEvent::Stop(*sound_name));
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct SpaceInvadersInputs {
+    pub credit: bool,
+    pub tilt: bool,
+    pub p1_start: bool,
+    pub p1_shoot: bool,
+    pub p1_left: bool,
+    pub p1_right: bool,
+    pub p2_start: bool,
+    pub p2_shoot: bool,
+    pub p2_left: bool,
+    pub p2_right: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_register_is_read_from_in_port_3() {
+        let mut ports = SpaceInvadersPorts::default();
+        ports.write_out_port(4, 0xAB).unwrap();
+        ports.write_out_port(4, 0xCD).unwrap();
+        ports.write_out_port(2, 0x04).unwrap();
+
+        assert_eq!(ports.read_in_port(3).unwrap(), 0xDA);
+    }
+
+    #[test]
+    fn sounds_are_only_played_when_their_bit_is_first_set() {
+        let mut ports = SpaceInvadersPorts::default();
+        ports.write_out_port(3, 0b0000_0010).unwrap();
+        ports.write_out_port(3, 0b0000_0010).unwrap();
+        ports.write_out_port(5, 0b0001_0000).unwrap();
+
+        assert_eq!(
+            ports.take_audio_events(),
+            vec![
+                AudioEvent::Play(SoundName::Shoot),
+                AudioEvent::Play(SoundName::UfoKilled)
+            ]
+        );
+        assert!(ports.take_audio_events().is_empty());
+    }
+
+    #[test]
+    fn only_the_ufo_sound_stops_when_its_bit_is_cleared() {
+        let mut ports = SpaceInvadersPorts::default();
+        ports.write_out_port(3, 0b0000_0001).unwrap();
+        ports.write_out_port(3, 0b0000_0011).unwrap();
+        ports.write_out_port(3, 0b0000_0001).unwrap();
+        ports.write_out_port(3, 0b0000_0000).unwrap();
+
+        assert_eq!(
+            ports.take_audio_events(),
+            vec![
+                AudioEvent::Play(SoundName::UfoFly),
+                AudioEvent::Play(SoundName::Shoot),
+                AudioEvent::Stop(SoundName::UfoFly)
+            ]
+        );
+    }
+
+    #[test]
+    fn loading_a_state_starts_or_stops_the_ufo_sound_to_match_it() {
+        let mut writer = SaveStateWriter::default();
+        SpaceInvadersPorts::default().save_state(&mut writer);
+        let bytes_without_ufo = writer.into_bytes();
+
+        let mut ports = SpaceInvadersPorts::default();
+        ports.write_out_port(3, 0b0000_0011).unwrap();
+        let mut writer = SaveStateWriter::default();
+        ports.save_state(&mut writer);
+        let bytes_with_ufo = writer.into_bytes();
+        ports.take_audio_events();
+
+        ports
+            .load_state(&mut SaveStateReader::new(&bytes_without_ufo))
+            .unwrap();
+        assert_eq!(
+            ports.take_audio_events(),
+            vec![AudioEvent::Stop(SoundName::UfoFly)]
+        );
+
+        ports
+            .load_state(&mut SaveStateReader::new(&bytes_with_ufo))
+            .unwrap();
+        assert_eq!(
+            ports.take_audio_events(),
+            vec![AudioEvent::Play(SoundName::UfoFly)]
+        );
+    }
+
+    #[test]
+    fn player_2_controls_are_only_set_in_in_port_2() {
+        let mut machine = SpaceInvadersMachine::default();
+        machine.set_input(Input::P2Left, true);
+        machine.set_ports_from_inputs();
+
+        let ports = &machine.state().ports;
+        assert_eq!(ports.read_in_port(0).unwrap(), 0b0010_1110);
+        assert_eq!(ports.read_in_port(1).unwrap(), 0b0000_1001);
+        assert_eq!(ports.read_in_port(2).unwrap(), 0b0010_0000);
+    }
+
+    #[test]
+    fn active_player_is_read_from_the_game_ram() {
+        let mut machine = SpaceInvadersMachine::default();
+        machine
+            .state_mut()
+            .write_memory(PLAYER_DATA_MSB_ADDRESS, 0x22);
+        assert_eq!(machine.active_player(), None);
+
+        machine.state_mut().write_memory(GAME_MODE_ADDRESS, 0x01);
+        assert_eq!(machine.active_player(), Some(2));
+
+        machine
+            .state_mut()
+            .write_memory(PLAYER_DATA_MSB_ADDRESS, 0x21);
+        assert_eq!(machine.active_player(), Some(1));
+    }
+
+    #[test]
+    fn dip_switches_are_set_in_in_port_2() {
+        let mut machine = SpaceInvadersMachine::default();
+        machine.set_dip_switch("ships", "5").unwrap();
+        machine.set_dip_switch("coin_info", "off").unwrap();
+        machine.set_ports_from_inputs();
+
+        assert_eq!(machine.state().ports.read_in_port(2).unwrap(), 0b1000_0010);
+    }
+
+    #[test]
+    fn inputs_are_set_in_the_in_ports() {
+        let mut machine = SpaceInvadersMachine::default();
+        machine.set_input(Input::Coin, true);
+        machine.set_input(Input::P1Fire, true);
+        machine.set_ports_from_inputs();
+
+        let ports = &machine.state().ports;
+        assert_eq!(ports.read_in_port(0).unwrap(), 0b0001_1110);
+        assert_eq!(ports.read_in_port(1).unwrap(), 0b0001_1000);
+        assert_eq!(ports.read_in_port(2).unwrap(), 0b0000_0000);
+    }
+}
@@ -1,19 +1,20 @@
-use crate::{Register, RegisterPair, State};
+use crate::{CpuError, Register, RegisterPair, State};
 // #[cfg(test)]
 // use mutagen::mutate;
 
 // #[cfg_attr(test, mutate)]
-pub fn push_instruction(state: &mut State, register_pair: RegisterPair) {
+pub fn push_instruction(state: &mut State, register_pair: RegisterPair) -> Result<(), CpuError> {
     if register_pair == RegisterPair::SP {
-        panic!("The register pair {register_pair} is not supported by the PUSH operation");
+        return Err(CpuError::UnsupportedRegisterPair(register_pair, "PUSH"));
     }
 
     let sp_minus_one = state.stack_pointer.wrapping_sub(1);
     let sp_minus_two = state.stack_pointer.wrapping_sub(2);
     let (register_pair_low, register_pair_high) = state.low_high_rp_value(register_pair);
-    state.memory[sp_minus_one as usize] = register_pair_high;
-    state.memory[sp_minus_two as usize] = register_pair_low;
+    state.write_memory(sp_minus_one, register_pair_high);
+    state.write_memory(sp_minus_two, register_pair_low);
     state.stack_pointer = sp_minus_two;
+    Ok(())
 }
 
 // #[cfg_attr(test, mutate)]
@@ -21,28 +22,29 @@ pub fn push_psw_instruction(state: &mut State) {
     let sp_minus_one = state.stack_pointer.wrapping_sub(1);
     let sp_minus_two = state.stack_pointer.wrapping_sub(2);
     let accumulator_value = state.registers[Register::A];
-    state.memory[sp_minus_one as usize] = accumulator_value;
+    state.write_memory(sp_minus_one, accumulator_value);
     let condition_flag_byte = state.condition_flag_byte();
-    state.memory[sp_minus_two as usize] = condition_flag_byte;
+    state.write_memory(sp_minus_two, condition_flag_byte);
     state.stack_pointer = sp_minus_two;
 }
 
 // #[cfg_attr(test, mutate)]
-pub fn pop_instruction(state: &mut State, register_pair: RegisterPair) {
+pub fn pop_instruction(state: &mut State, register_pair: RegisterPair) -> Result<(), CpuError> {
     if register_pair == RegisterPair::SP {
-        panic!("The register pair {register_pair} is not supported by the POP operation");
+        return Err(CpuError::UnsupportedRegisterPair(register_pair, "POP"));
     }
 
     let sp_plus_one = state.stack_pointer.wrapping_add(1);
     let sp_plus_two = state.stack_pointer.wrapping_add(2);
-    let value_for_register_pair_low = state.memory[state.stack_pointer as usize];
-    let value_for_register_pair_high = state.memory[sp_plus_one as usize];
+    let value_for_register_pair_low = state.read_memory(state.stack_pointer);
+    let value_for_register_pair_high = state.read_memory(sp_plus_one);
     state.set_low_high_rp_value(
         register_pair,
         value_for_register_pair_low,
         value_for_register_pair_high,
     );
     state.stack_pointer = sp_plus_two;
+    Ok(())
 }
 
 // #[cfg_attr(test, mutate)]
@@ -50,7 +52,7 @@ pub fn pop_psw_instruction(state: &mut State) {
     let sp_plus_one = state.stack_pointer.wrapping_add(1);
     let sp_plus_two = state.stack_pointer.wrapping_add(2);
     state.set_condition_flag_byte(state.stack_pointer);
-    let accumulator_value = state.memory[sp_plus_one as usize];
+    let accumulator_value = state.read_memory(sp_plus_one);
     state.registers[Register::A] = accumulator_value;
     state.stack_pointer = sp_plus_two;
 }
@@ -58,14 +60,14 @@ pub fn pop_psw_instruction(state: &mut State) {
 // #[cfg_attr(test, mutate)]
 pub fn xthl_instruction(state: &mut State) {
     let sp_plus_one = state.stack_pointer.wrapping_add(1);
-    let low_memory_value = state.memory[state.stack_pointer as usize];
-    let high_memory_value = state.memory[sp_plus_one as usize];
+    let low_memory_value = state.read_memory(state.stack_pointer);
+    let high_memory_value = state.read_memory(sp_plus_one);
     let l_register_value = state.registers[Register::L];
     let h_register_value = state.registers[Register::H];
     state.registers[Register::L] = low_memory_value;
-    state.memory[state.stack_pointer as usize] = l_register_value;
+    state.write_memory(state.stack_pointer, l_register_value);
     state.registers[Register::H] = high_memory_value;
-    state.memory[sp_plus_one as usize] = h_register_value;
+    state.write_memory(sp_plus_one, h_register_value);
 }
 
 // #[cfg_attr(test, mutate)]
@@ -101,7 +103,7 @@ mod tests {
             .register_values(hashmap! { Register::B => 221, Register::C => 101 })
             .stack_pointer(0xF028)
             .build();
-        push_instruction(&mut state, RegisterPair::BC);
+        push_instruction(&mut state, RegisterPair::BC).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -113,10 +115,12 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "The register pair SP is not supported by the PUSH operation")]
     fn push_does_not_support_stack_pointer_as_given_register_pair() {
         let mut state = State::default();
-        push_instruction(&mut state, RegisterPair::SP);
+        assert_eq!(
+            push_instruction(&mut state, RegisterPair::SP),
+            Err(CpuError::UnsupportedRegisterPair(RegisterPair::SP, "PUSH"))
+        );
     }
 
     #[test]
@@ -156,7 +160,7 @@ mod tests {
             .stack_pointer(0x8CCD)
             .memory_values(hashmap! { 0x8CCC => 102, 0x8CCD => 40, 0x8CCE => 204, 0x8CCF => 16 })
             .build();
-        pop_instruction(&mut state, RegisterPair::DE);
+        pop_instruction(&mut state, RegisterPair::DE).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -170,10 +174,12 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "The register pair SP is not supported by the POP operation")]
     fn pop_does_not_support_stack_pointer_as_given_register_pair() {
         let mut state = State::default();
-        pop_instruction(&mut state, RegisterPair::SP);
+        assert_eq!(
+            pop_instruction(&mut state, RegisterPair::SP),
+            Err(CpuError::UnsupportedRegisterPair(RegisterPair::SP, "POP"))
+        );
     }
 
     #[test]
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::machine::{AudioEvent, SoundKind, SoundName};
+
+// The frontend opens the mixer at this rate, with unsigned 8-bit mono samples
+pub const SAMPLE_RATE: u32 = 11_025;
+const SILENCE: u8 = 0x80;
+// Tones are quieter than noise, as they sound louder at the same level
+const SWEPT_AMPLITUDE: u8 = 0x40;
+
+pub const TONE_AMPLITUDE: u8 = 0x30;
+
+// Exactly one second, which holds a whole number of periods so the wave loops without a click
+pub fn square_wave(frequency: u32, amplitude: u8) -> Vec<u8> {
+    (0..SAMPLE_RATE)
+        .map(|index| {
+            let phase = u64::from(index) * u64::from(frequency) * 2 / u64::from(SAMPLE_RATE);
+            if phase.is_multiple_of(2) {
+                SILENCE.saturating_add(amplitude)
+            } else {
+                SILENCE.saturating_sub(amplitude)
+            }
+        })
+        .collect()
+}
+
+// A stand-in for sounds that have no sample file. Looped sounds repeat without a click.
+pub fn synthesize(sound_name: SoundName) -> Vec<u8> {
+    match sound_name {
+        SoundName::Shoot => noise_burst(300, 1, 10.0),
+        SoundName::PlayerKilled => noise_burst(1_000, 8, 3.0),
+        // A quick downward chirp
+        SoundName::InvaderKilled => swept_square_wave(200, 8.0, |time| 1_200.0 - 4_000.0 * time),
+        // A siren that rises and falls four times a second, which makes 225 whole cycles
+        SoundName::UfoFly => swept_square_wave(250, 0.0, |time| {
+            let sweep_position = (time * 4.0).fract();
+            600.0 + 600.0 * (1.0 - (2.0 * sweep_position - 1.0).abs())
+        }),
+        // A warble between two high notes that fades out
+        SoundName::UfoKilled => swept_square_wave(1_000, 1.5, |time| {
+            if ((time * 16.0) as u32).is_multiple_of(2) {
+                1_600.0
+            } else {
+                1_200.0
+            }
+        }),
+        // The four descending notes of the invaders' march
+        SoundName::InvaderMovement1 => swept_square_wave(100, 20.0, |_| 110.0),
+        SoundName::InvaderMovement2 => swept_square_wave(100, 20.0, |_| 98.0),
+        SoundName::InvaderMovement3 => swept_square_wave(100, 20.0, |_| 87.0),
+        SoundName::InvaderMovement4 => swept_square_wave(100, 20.0, |_| 82.0),
+        // A sharp crack of noise
+        SoundName::LeftShot | SoundName::RightShot => noise_burst(250, 1, 8.0),
+        // Lower, rumbling noise that rings on for longer
+        SoundName::LeftHit | SoundName::RightHit => noise_burst(600, 6, 4.0),
+    }
+}
+
+// The frequency in Hz is given for each point in time in seconds
+fn swept_square_wave(
+    duration_ms: u32,
+    decay_rate: f32,
+    frequency_at: impl Fn(f32) -> f32,
+) -> Vec<u8> {
+    let num_samples = SAMPLE_RATE * duration_ms / 1_000;
+    let mut phase = 0.0_f32;
+
+    (0..num_samples)
+        .map(|index| {
+            let time = index as f32 / SAMPLE_RATE as f32;
+            let offset = (f32::from(SWEPT_AMPLITUDE) * (-decay_rate * time).exp()) as u8;
+            let sample = if phase < 0.5 {
+                SILENCE + offset
+            } else {
+                SILENCE - offset
+            };
+            phase = (phase + frequency_at(time) / SAMPLE_RATE as f32).fract();
+            sample
+        })
+        .collect()
+}
+
+// White noise that fades out exponentially. Holding each noise sample for longer lowers its pitch.
+fn noise_burst(duration_ms: u32, hold_samples: u32, decay_rate: f32) -> Vec<u8> {
+    let num_samples = SAMPLE_RATE * duration_ms / 1_000;
+    let mut noise = Noise::default();
+    let mut is_high = false;
+
+    (0..num_samples)
+        .map(|index| {
+            if index.is_multiple_of(hold_samples) {
+                is_high = noise.next_bit();
+            }
+            let envelope = (-decay_rate * index as f32 / SAMPLE_RATE as f32).exp();
+            let offset = (f32::from(SILENCE - 1) * envelope) as u8;
+            if is_high {
+                SILENCE + offset
+            } else {
+                SILENCE - offset
+            }
+        })
+        .collect()
+}
+
+// A 16-bit linear feedback shift register, so the noise is the same every time
+struct Noise {
+    register: u16,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise { register: 0xACE1 }
+    }
+}
+
+impl Noise {
+    fn next_bit(&mut self) -> bool {
+        let bit = self.register & 1 == 1;
+        self.register >>= 1;
+        if bit {
+            self.register ^= 0xB400;
+        }
+        bit
+    }
+}
+
+struct Voice {
+    // None for the tone
+    sound_name: Option<SoundName>,
+    samples: Rc<[u8]>,
+    position: usize,
+    kind: SoundKind,
+}
+
+// Mixes the machine's sounds in software, for when there is no audio device such as when running
+// headless. Sounds come from sample files if they are added, or are synthesized. Like the sound
+// circuits on the boards, each sound has one voice, so playing it again restarts it.
+pub struct Mixer {
+    sounds: HashMap<SoundName, Rc<[u8]>>,
+    voices: Vec<Voice>,
+    // As a percentage
+    volume: u8,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Mixer {
+            sounds: HashMap::new(),
+            voices: Vec::new(),
+            volume: 100,
+        }
+    }
+}
+
+impl Mixer {
+    pub fn set_volume(&mut self, volume: u8) {
+        self.volume = volume.min(100);
+    }
+
+    pub fn set_sound(&mut self, sound_name: SoundName, samples: Vec<u8>) {
+        self.sounds.insert(sound_name, samples.into());
+    }
+
+    pub fn handle_event(&mut self, audio_event: AudioEvent) {
+        match audio_event {
+            AudioEvent::Play(sound_name) => {
+                let samples = self
+                    .sounds
+                    .entry(sound_name)
+                    .or_insert_with(|| synthesize(sound_name).into())
+                    .clone();
+                self.voices
+                    .retain(|voice| voice.sound_name != Some(sound_name));
+                self.voices.push(Voice {
+                    sound_name: Some(sound_name),
+                    samples,
+                    position: 0,
+                    kind: sound_name.kind(),
+                });
+            }
+            AudioEvent::Stop(sound_name) => self
+                .voices
+                .retain(|voice| voice.sound_name != Some(sound_name)),
+            AudioEvent::SetTone(frequency) => {
+                self.voices.retain(|voice| voice.sound_name.is_some());
+                if let Some(frequency) = frequency {
+                    self.voices.push(Voice {
+                        sound_name: None,
+                        samples: square_wave(frequency, TONE_AMPLITUDE).into(),
+                        position: 0,
+                        kind: SoundKind::Looped,
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.voices.is_empty()
+    }
+
+    // Voices are added together and scaled by the volume, then clipped if they get too loud
+    pub fn render(&mut self, num_samples: usize) -> Vec<u8> {
+        let mut mixed = Vec::with_capacity(num_samples);
+
+        for _ in 0..num_samples {
+            let mut total = 0_i32;
+            for voice in &mut self.voices {
+                if voice.position == voice.samples.len() && voice.kind == SoundKind::Looped {
+                    voice.position = 0;
+                }
+                if let Some(sample) = voice.samples.get(voice.position) {
+                    total += i32::from(*sample) - i32::from(SILENCE);
+                    voice.position += 1;
+                }
+            }
+            let scaled_total = total * i32::from(self.volume) / 100;
+            mixed.push((scaled_total + i32::from(SILENCE)).clamp(0, 0xFF) as u8);
+        }
+
+        self.voices.retain(|voice| {
+            voice.kind == SoundKind::Looped || voice.position < voice.samples.len()
+        });
+        mixed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_wave_has_the_requested_number_of_cycles_per_second() {
+        let wave = square_wave(441, 0x20);
+        assert_eq!(wave.len(), SAMPLE_RATE as usize);
+
+        let rising_edges = wave.windows(2).filter(|pair| pair[0] < pair[1]).count();
+        // The first cycle starts high, so it has no rising edge
+        assert_eq!(rising_edges, 440);
+        assert!(wave.iter().all(|sample| *sample == 0xA0 || *sample == 0x60));
+    }
+
+    #[test]
+    fn noise_bursts_fade_to_silence() {
+        let burst = synthesize(SoundName::LeftShot);
+        assert_eq!(burst.len(), SAMPLE_RATE as usize / 4);
+        assert!(
+            burst[..100]
+                .iter()
+                .any(|sample| sample.abs_diff(SILENCE) > 100)
+        );
+        assert!(
+            burst[burst.len() - 100..]
+                .iter()
+                .all(|sample| sample.abs_diff(SILENCE) < 20)
+        );
+        assert_eq!(burst, synthesize(SoundName::RightShot));
+    }
+
+    #[test]
+    fn looped_sounds_join_up_without_a_jump() {
+        let siren = synthesize(SoundName::UfoFly);
+        assert_eq!(siren.len(), SAMPLE_RATE as usize / 4);
+
+        let mut looped_siren = siren.clone();
+        looped_siren.push(siren[0]);
+        let rising_edges = looped_siren
+            .windows(2)
+            .filter(|pair| pair[0] < pair[1])
+            .count();
+        assert_eq!(rising_edges, 225);
+    }
+
+    #[test]
+    fn mixer_adds_voices_together_and_drops_finished_one_shots() {
+        let mut mixer = Mixer::default();
+        mixer.set_sound(SoundName::Shoot, vec![0x90, 0x90]);
+        mixer.set_sound(SoundName::UfoFly, vec![0x70, 0xFF, 0x00]);
+        assert_eq!(mixer.render(2), vec![SILENCE, SILENCE]);
+
+        mixer.handle_event(AudioEvent::Play(SoundName::Shoot));
+        mixer.handle_event(AudioEvent::Play(SoundName::UfoFly));
+        assert_eq!(mixer.render(5), vec![0x80, 0xFF, 0x00, 0x70, 0xFF]);
+        assert!(mixer.is_playing());
+
+        mixer.handle_event(AudioEvent::Stop(SoundName::UfoFly));
+        assert!(!mixer.is_playing());
+        assert_eq!(mixer.render(1), vec![SILENCE]);
+    }
+
+    #[test]
+    fn mixer_restarts_a_sound_that_is_played_again_and_scales_by_the_volume() {
+        let mut mixer = Mixer::default();
+        mixer.set_sound(SoundName::Shoot, vec![0x90, 0xA0, 0xB0]);
+        mixer.set_volume(50);
+
+        mixer.handle_event(AudioEvent::Play(SoundName::Shoot));
+        assert_eq!(mixer.render(2), vec![0x88, 0x90]);
+        mixer.handle_event(AudioEvent::Play(SoundName::Shoot));
+        assert_eq!(mixer.render(4), vec![0x88, 0x90, 0x98, SILENCE]);
+    }
+
+    #[test]
+    fn mixer_plays_one_tone_at_a_time() {
+        let mut mixer = Mixer::default();
+        mixer.handle_event(AudioEvent::SetTone(Some(1_000)));
+        mixer.handle_event(AudioEvent::SetTone(Some(500)));
+        assert_eq!(mixer.voices.len(), 1);
+
+        let samples = mixer.render(SAMPLE_RATE as usize + 1);
+        assert_eq!(samples[0], samples[SAMPLE_RATE as usize]);
+
+        mixer.handle_event(AudioEvent::SetTone(None));
+        assert!(!mixer.is_playing());
+    }
+}
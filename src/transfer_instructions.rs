@@ -1,4 +1,4 @@
-use crate::{Register, RegisterPair, State, bit_operations};
+use crate::{CpuError, Register, RegisterPair, State, bit_operations};
 // #[cfg(test)]
 // use mutagen::mutate;
 
@@ -11,7 +11,7 @@ pub fn mov_instruction(state: &mut State, from_register: Register, to_register:
 // #[cfg_attr(test, mutate)]
 pub fn mov_from_mem_instruction(state: &mut State, register: Register) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    let data = state.memory[memory_address as usize];
+    let data = state.read_memory(memory_address);
     mvi_instruction(state, register, data);
 }
 
@@ -29,7 +29,7 @@ pub fn mvi_instruction(state: &mut State, register: Register, data: u8) {
 // #[cfg_attr(test, mutate)]
 pub fn mvi_mem_instruction(state: &mut State, data: u8) {
     let memory_address = state.full_rp_value(RegisterPair::HL);
-    state.memory[memory_address as usize] = data;
+    state.write_memory(memory_address, data);
 }
 
 // #[cfg_attr(test, mutate)]
@@ -45,7 +45,7 @@ pub fn lxi_instruction(
 // #[cfg_attr(test, mutate)]
 pub fn lda_instruction(state: &mut State, low_data: u8, high_data: u8) {
     let memory_address = bit_operations::concat_low_high_bytes(low_data, high_data);
-    let memory_location_value = state.memory[memory_address as usize];
+    let memory_location_value = state.read_memory(memory_address);
     state.registers[Register::A] = memory_location_value;
 }
 
@@ -53,15 +53,15 @@ pub fn lda_instruction(state: &mut State, low_data: u8, high_data: u8) {
 pub fn sta_instruction(state: &mut State, low_data: u8, high_data: u8) {
     let memory_address = bit_operations::concat_low_high_bytes(low_data, high_data);
     let accumulator_value = state.registers[Register::A];
-    state.memory[memory_address as usize] = accumulator_value;
+    state.write_memory(memory_address, accumulator_value);
 }
 
 // #[cfg_attr(test, mutate)]
 pub fn lhld_instruction(state: &mut State, low_data: u8, high_data: u8) {
     let first_memory_address = bit_operations::concat_low_high_bytes(low_data, high_data);
     let second_memory_address = first_memory_address.wrapping_add(1);
-    let first_memory_value = state.memory[first_memory_address as usize];
-    let second_memory_value = state.memory[second_memory_address as usize];
+    let first_memory_value = state.read_memory(first_memory_address);
+    let second_memory_value = state.read_memory(second_memory_address);
     state.registers[Register::L] = first_memory_value;
     state.registers[Register::H] = second_memory_value;
 }
@@ -72,30 +72,32 @@ pub fn shld_instruction(state: &mut State, low_data: u8, high_data: u8) {
     let second_memory_address = first_memory_address.wrapping_add(1);
     let h_register_value = state.registers[Register::H];
     let l_register_value = state.registers[Register::L];
-    state.memory[first_memory_address as usize] = l_register_value;
-    state.memory[second_memory_address as usize] = h_register_value;
+    state.write_memory(first_memory_address, l_register_value);
+    state.write_memory(second_memory_address, h_register_value);
 }
 
 // #[cfg_attr(test, mutate)]
-pub fn ldax_instruction(state: &mut State, register_pair: RegisterPair) {
+pub fn ldax_instruction(state: &mut State, register_pair: RegisterPair) -> Result<(), CpuError> {
     if register_pair == RegisterPair::HL || register_pair == RegisterPair::SP {
-        panic!("The register pair {register_pair} is not supported by the LDAX operation");
+        return Err(CpuError::UnsupportedRegisterPair(register_pair, "LDAX"));
     }
 
     let memory_address = state.full_rp_value(register_pair);
-    let value = state.memory[memory_address as usize];
+    let value = state.read_memory(memory_address);
     state.registers[Register::A] = value;
+    Ok(())
 }
 
 // #[cfg_attr(test, mutate)]
-pub fn stax_instruction(state: &mut State, register_pair: RegisterPair) {
+pub fn stax_instruction(state: &mut State, register_pair: RegisterPair) -> Result<(), CpuError> {
     if register_pair == RegisterPair::HL || register_pair == RegisterPair::SP {
-        panic!("The register pair {register_pair} is not supported by the STAX operation");
+        return Err(CpuError::UnsupportedRegisterPair(register_pair, "STAX"));
     }
 
     let value = state.registers[Register::A];
     let memory_address = state.full_rp_value(register_pair);
-    state.memory[memory_address as usize] = value;
+    state.write_memory(memory_address, value);
+    Ok(())
 }
 
 // #[cfg_attr(test, mutate)]
@@ -429,7 +431,7 @@ mod tests {
             .register_values(hashmap! { Register::D => 43, Register::E => 230 })
             .memory_values(hashmap! { 0x2BE5 => 27, 0x2BE6 => 107, 0x2BE7 => 243})
             .build();
-        ldax_instruction(&mut state, RegisterPair::DE);
+        ldax_instruction(&mut state, RegisterPair::DE).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -446,7 +448,7 @@ mod tests {
         let mut state = StateBuilder::default()
             .memory_values(hashmap! { 0x0000 => 101 })
             .build();
-        ldax_instruction(&mut state, RegisterPair::BC);
+        ldax_instruction(&mut state, RegisterPair::BC).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -461,7 +463,7 @@ mod tests {
         let mut state = StateBuilder::default()
             .register_values(hashmap! { Register::B => 47, Register::C => 31, Register::A => 247 })
             .build();
-        ldax_instruction(&mut state, RegisterPair::BC);
+        ldax_instruction(&mut state, RegisterPair::BC).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -471,17 +473,21 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "The register pair HL is not supported by the LDAX operation")]
     fn ldax_does_not_support_the_hl_register_pair() {
         let mut state = State::default();
-        ldax_instruction(&mut state, RegisterPair::HL);
+        assert_eq!(
+            ldax_instruction(&mut state, RegisterPair::HL),
+            Err(CpuError::UnsupportedRegisterPair(RegisterPair::HL, "LDAX"))
+        );
     }
 
     #[test]
-    #[should_panic(expected = "The register pair SP is not supported by the LDAX operation")]
     fn ldax_does_not_support_the_sp_register_pair() {
         let mut state = State::default();
-        ldax_instruction(&mut state, RegisterPair::SP);
+        assert_eq!(
+            ldax_instruction(&mut state, RegisterPair::SP),
+            Err(CpuError::UnsupportedRegisterPair(RegisterPair::SP, "LDAX"))
+        );
     }
 
     #[test]
@@ -489,7 +495,7 @@ mod tests {
         let mut state = StateBuilder::default()
             .register_values(hashmap! { Register::D => 160, Register::E => 17, Register::A => 222 })
             .build();
-        stax_instruction(&mut state, RegisterPair::DE);
+        stax_instruction(&mut state, RegisterPair::DE).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -506,7 +512,7 @@ mod tests {
         let mut state = StateBuilder::default()
             .register_values(hashmap! { Register::A => 107 })
             .build();
-        stax_instruction(&mut state, RegisterPair::DE);
+        stax_instruction(&mut state, RegisterPair::DE).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -522,7 +528,7 @@ mod tests {
             .register_values(hashmap! { Register::B => 218, Register::C => 15 })
             .memory_values(hashmap! { 0xDA0F => 174 })
             .build();
-        stax_instruction(&mut state, RegisterPair::BC);
+        stax_instruction(&mut state, RegisterPair::BC).unwrap();
         assert_state_is_as_expected(
             &state,
             &StateBuilder::default()
@@ -532,17 +538,21 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "The register pair HL is not supported by the STAX operation")]
     fn stax_does_not_support_the_hl_register_pair() {
         let mut state = State::default();
-        stax_instruction(&mut state, RegisterPair::HL);
+        assert_eq!(
+            stax_instruction(&mut state, RegisterPair::HL),
+            Err(CpuError::UnsupportedRegisterPair(RegisterPair::HL, "STAX"))
+        );
     }
 
     #[test]
-    #[should_panic(expected = "The register pair SP is not supported by the STAX operation")]
     fn stax_does_not_support_the_sp_register_pair() {
         let mut state = State::default();
-        stax_instruction(&mut state, RegisterPair::SP);
+        assert_eq!(
+            stax_instruction(&mut state, RegisterPair::SP),
+            Err(CpuError::UnsupportedRegisterPair(RegisterPair::SP, "STAX"))
+        );
     }
 
     #[test]
@@ -0,0 +1,304 @@
+use std::ops::RangeInclusive;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn includes(self, access: Access) -> bool {
+        match self {
+            WatchKind::Read => access == Access::Read,
+            WatchKind::Write => access == Access::Write,
+            WatchKind::ReadWrite => true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PortDirection {
+    In,
+    Out,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Watchpoint {
+    pub addresses: RangeInclusive<u16>,
+    pub kind: WatchKind,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PortBreakpoint {
+    pub port_number: u8,
+    pub direction: PortDirection,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BreakTrigger {
+    Memory {
+        access: Access,
+        address: u16,
+    },
+    Port {
+        direction: PortDirection,
+        port_number: u8,
+    },
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct BreakEvent {
+    pub program_counter: u16,
+    pub trigger: BreakTrigger,
+    pub value: u8,
+}
+
+impl std::fmt::Display for BreakEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.trigger {
+            BreakTrigger::Memory {
+                access: Access::Read,
+                address,
+            } => write!(f, "Read {:02X} from {address:04X}", self.value)?,
+            BreakTrigger::Memory {
+                access: Access::Write,
+                address,
+            } => write!(f, "Wrote {:02X} to {address:04X}", self.value)?,
+            BreakTrigger::Port {
+                direction: PortDirection::In,
+                port_number,
+            } => write!(f, "Read {:02X} from port {port_number:02X}", self.value)?,
+            BreakTrigger::Port {
+                direction: PortDirection::Out,
+                port_number,
+            } => write!(f, "Wrote {:02X} to port {port_number:02X}", self.value)?,
+        }
+        write!(f, " (PC: {:04X})", self.program_counter)
+    }
+}
+
+impl BreakEvent {
+    // What the user set up to cause the break, for messages
+    pub fn breakpoint_kind(&self) -> &'static str {
+        match self.trigger {
+            BreakTrigger::Memory { .. } => "watchpoint",
+            BreakTrigger::Port { .. } => "port breakpoint",
+        }
+    }
+}
+
+// Running stops after an instruction that triggers, so the host can inspect the state. If an
+// instruction triggers more than once, such as PUSH writing to two watched addresses, only the
+// first is kept.
+#[derive(Default)]
+pub struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    port_breakpoints: Vec<PortBreakpoint>,
+    triggered_event: Option<BreakEvent>,
+}
+
+impl Watchpoints {
+    pub fn add_watchpoint(&mut self, addresses: RangeInclusive<u16>, kind: WatchKind) {
+        self.watchpoints.push(Watchpoint { addresses, kind });
+    }
+
+    pub fn remove_watchpoints_at(&mut self, address: u16) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints
+            .retain(|watchpoint| !watchpoint.addresses.contains(&address));
+        self.watchpoints.len() != count
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn add_port_breakpoint(&mut self, port_number: u8, direction: PortDirection) {
+        let port_breakpoint = PortBreakpoint {
+            port_number,
+            direction,
+        };
+        if !self.port_breakpoints.contains(&port_breakpoint) {
+            self.port_breakpoints.push(port_breakpoint);
+        }
+    }
+
+    pub fn remove_port_breakpoint(&mut self, port_number: u8, direction: PortDirection) -> bool {
+        let count = self.port_breakpoints.len();
+        self.port_breakpoints.retain(|port_breakpoint| {
+            port_breakpoint.port_number != port_number || port_breakpoint.direction != direction
+        });
+        self.port_breakpoints.len() != count
+    }
+
+    pub fn port_breakpoints(&self) -> &[PortBreakpoint] {
+        &self.port_breakpoints
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty() && self.port_breakpoints.is_empty()
+    }
+
+    // Any later events from the same instruction have already been dropped
+    pub fn take_triggered_event(&mut self) -> Option<BreakEvent> {
+        self.triggered_event.take()
+    }
+
+    pub(crate) fn check_memory_access(
+        &mut self,
+        program_counter: u16,
+        access: Access,
+        address: u16,
+        value: u8,
+    ) {
+        if self.watchpoints.iter().any(|watchpoint| {
+            watchpoint.kind.includes(access) && watchpoint.addresses.contains(&address)
+        }) {
+            self.trigger(
+                program_counter,
+                BreakTrigger::Memory { access, address },
+                value,
+            );
+        }
+    }
+
+    pub(crate) fn check_port_access(
+        &mut self,
+        program_counter: u16,
+        direction: PortDirection,
+        port_number: u8,
+        value: u8,
+    ) {
+        if self.port_breakpoints.contains(&PortBreakpoint {
+            port_number,
+            direction,
+        }) {
+            self.trigger(
+                program_counter,
+                BreakTrigger::Port {
+                    direction,
+                    port_number,
+                },
+                value,
+            );
+        }
+    }
+
+    fn trigger(&mut self, program_counter: u16, trigger: BreakTrigger, value: u8) {
+        self.triggered_event.get_or_insert(BreakEvent {
+            program_counter,
+            trigger,
+            value,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Operation, Register, StateBuilder};
+    use maplit::hashmap;
+
+    #[test]
+    fn write_watchpoints_trigger_on_writes_inside_their_range() {
+        let mut state = StateBuilder::default()
+            .stack_pointer(0x2402)
+            .program_counter(0x0100)
+            .build();
+        state
+            .watchpoints
+            .add_watchpoint(0x2400..=0x2400, WatchKind::Write);
+        state.run_operation(&Operation::PushPsw);
+
+        assert_eq!(
+            state.watchpoints.take_triggered_event(),
+            Some(BreakEvent {
+                program_counter: 0x0100,
+                trigger: BreakTrigger::Memory {
+                    access: Access::Write,
+                    address: 0x2400,
+                },
+                value: 0b0000_0010,
+            })
+        );
+        assert_eq!(state.watchpoints.take_triggered_event(), None);
+    }
+
+    #[test]
+    fn read_watchpoints_ignore_writes_and_instruction_fetches() {
+        let mut state = StateBuilder::default()
+            .memory_values(hashmap! { 0x0001 => 0x55 })
+            .register_values(hashmap! { Register::H => 0x20 })
+            .build();
+        state
+            .watchpoints
+            .add_watchpoint(0x0000..=0x2000, WatchKind::Read);
+
+        state.run_operation(&Operation::Mvi(Register::B));
+        state.run_operation(&Operation::MovToMem(Register::B));
+        assert_eq!(state.watchpoints.take_triggered_event(), None);
+
+        state.run_operation(&Operation::MovFromMem(Register::C));
+        assert_eq!(
+            state.watchpoints.take_triggered_event(),
+            Some(BreakEvent {
+                program_counter: 0x0003,
+                trigger: BreakTrigger::Memory {
+                    access: Access::Read,
+                    address: 0x2000,
+                },
+                value: 0x55,
+            })
+        );
+    }
+
+    #[test]
+    fn port_breakpoints_trigger_on_matching_direction_and_port() {
+        let mut state = StateBuilder::default()
+            .memory_values(hashmap! { 0x0001 => 0x03, 0x0003 => 0x03 })
+            .register_values(hashmap! { Register::A => 0xAB })
+            .build();
+        state
+            .watchpoints
+            .add_port_breakpoint(0x03, PortDirection::Out);
+
+        state.run_operation(&Operation::In);
+        assert_eq!(state.watchpoints.take_triggered_event(), None);
+
+        state.run_operation(&Operation::Out);
+        let event = state.watchpoints.take_triggered_event().unwrap();
+        assert_eq!(event.to_string(), "Wrote 00 to port 03 (PC: 0002)");
+
+        assert!(
+            state
+                .watchpoints
+                .remove_port_breakpoint(0x03, PortDirection::Out)
+        );
+        assert!(state.watchpoints.is_empty());
+    }
+
+    #[test]
+    fn removing_watchpoints_at_an_address_removes_every_overlapping_range() {
+        let mut watchpoints = Watchpoints::default();
+        watchpoints.add_watchpoint(0x2000..=0x20FF, WatchKind::Read);
+        watchpoints.add_watchpoint(0x2010..=0x2010, WatchKind::Write);
+        watchpoints.add_watchpoint(0x3000..=0x3000, WatchKind::ReadWrite);
+
+        assert!(watchpoints.remove_watchpoints_at(0x2010));
+        assert!(!watchpoints.remove_watchpoints_at(0x2010));
+        assert_eq!(
+            watchpoints.watchpoints(),
+            &[Watchpoint {
+                addresses: 0x3000..=0x3000,
+                kind: WatchKind::ReadWrite,
+            }]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use crate::synth::SAMPLE_RATE;
+
+const PCM_FORMAT: u16 = 1;
+const FORMAT_CHUNK_SIZE: u32 = 16;
+
+// Only the format the emulator mixes in is supported: unsigned 8-bit mono PCM at its sample rate
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum WavError {
+    NotAWavFile,
+    Truncated,
+    UnsupportedFormat {
+        channels: u16,
+        sample_rate: u32,
+        bits_per_sample: u16,
+    },
+}
+
+impl std::fmt::Display for WavError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WavError::NotAWavFile => write!(f, "Not a PCM wav file"),
+            WavError::Truncated => write!(f, "Wav file ended unexpectedly"),
+            WavError::UnsupportedFormat {
+                channels,
+                sample_rate,
+                bits_per_sample,
+            } => write!(
+                f,
+                "Expected 8-bit mono audio at {SAMPLE_RATE} Hz, found {bits_per_sample}-bit audio \
+                with {channels} channels at {sample_rate} Hz"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WavError {}
+
+fn read_u16(bytes: &[u8], position: usize) -> Result<u16, WavError> {
+    bytes
+        .get(position..position + 2)
+        .map(|field| u16::from_le_bytes(field.try_into().unwrap()))
+        .ok_or(WavError::Truncated)
+}
+
+fn read_u32(bytes: &[u8], position: usize) -> Result<u32, WavError> {
+    bytes
+        .get(position..position + 4)
+        .map(|field| u32::from_le_bytes(field.try_into().unwrap()))
+        .ok_or(WavError::Truncated)
+}
+
+pub fn encode(samples: &[u8]) -> Vec<u8> {
+    let data_size = samples.len() as u32;
+    let mut bytes = Vec::with_capacity(samples.len() + 45);
+
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size + data_size % 2).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&FORMAT_CHUNK_SIZE.to_le_bytes());
+    bytes.extend_from_slice(&PCM_FORMAT.to_le_bytes());
+    bytes.extend_from_slice(&1_u16.to_le_bytes());
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    // Bytes per second and per sample frame, which are the same as the sample rate for 8-bit mono
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&1_u16.to_le_bytes());
+    bytes.extend_from_slice(&8_u16.to_le_bytes());
+
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    bytes.extend_from_slice(samples);
+    // Chunks are padded to an even length
+    if data_size % 2 == 1 {
+        bytes.push(0x00);
+    }
+    bytes
+}
+
+// Returns the samples. Chunks other than the format and the data, such as metadata, are skipped.
+pub fn decode(bytes: &[u8]) -> Result<Vec<u8>, WavError> {
+    if bytes.get(0..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"WAVE") {
+        return Err(WavError::NotAWavFile);
+    }
+
+    let mut position = 12;
+    let mut has_format = false;
+    loop {
+        let chunk_id = bytes
+            .get(position..position + 4)
+            .ok_or(WavError::Truncated)?;
+        let chunk_size = read_u32(bytes, position + 4)? as usize;
+        let chunk_start = position + 8;
+
+        match chunk_id {
+            b"fmt " => {
+                if read_u16(bytes, chunk_start)? != PCM_FORMAT {
+                    return Err(WavError::NotAWavFile);
+                }
+                let channels = read_u16(bytes, chunk_start + 2)?;
+                let sample_rate = read_u32(bytes, chunk_start + 4)?;
+                let bits_per_sample = read_u16(bytes, chunk_start + 14)?;
+                if (channels, sample_rate, bits_per_sample) != (1, SAMPLE_RATE, 8) {
+                    return Err(WavError::UnsupportedFormat {
+                        channels,
+                        sample_rate,
+                        bits_per_sample,
+                    });
+                }
+                has_format = true;
+            }
+            b"data" if has_format => {
+                return bytes
+                    .get(chunk_start..chunk_start + chunk_size)
+                    .map(<[u8]>::to_vec)
+                    .ok_or(WavError::Truncated);
+            }
+            b"data" => return Err(WavError::NotAWavFile),
+            _ => {}
+        }
+
+        position = chunk_start + chunk_size + chunk_size % 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_samples_decode_to_the_same_samples() {
+        let samples = [0x80, 0xFF, 0x00];
+        let bytes = encode(&samples);
+
+        assert_eq!(bytes.len(), 44 + 4);
+        assert_eq!(read_u32(&bytes, 4), Ok(40));
+        assert_eq!(decode(&bytes), Ok(samples.to_vec()));
+    }
+
+    #[test]
+    fn decode_skips_other_chunks_and_rejects_other_formats() {
+        let mut bytes = encode(&[0x80, 0x81]);
+        let list_chunk = [
+            b"LIST".as_slice(),
+            &3_u32.to_le_bytes(),
+            &[0x01, 0x02, 0x03, 0x00],
+        ]
+        .concat();
+        bytes.splice(36..36, list_chunk);
+        assert_eq!(decode(&bytes), Ok(vec![0x80, 0x81]));
+
+        bytes[22] = 0x02;
+        assert_eq!(
+            decode(&bytes),
+            Err(WavError::UnsupportedFormat {
+                channels: 2,
+                sample_rate: SAMPLE_RATE,
+                bits_per_sample: 8
+            })
+        );
+
+        assert_eq!(decode(b"RIFF"), Err(WavError::NotAWavFile));
+        assert_eq!(decode(&bytes[..30]), Err(WavError::Truncated));
+    }
+}
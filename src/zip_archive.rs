@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+
+use crate::checksum;
+
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4B50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4B50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4B50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const CENTRAL_DIRECTORY_HEADER_SIZE: usize = 46;
+const LOCAL_HEADER_SIZE: usize = 30;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ZipError {
+    NotAZipFile,
+    Truncated,
+    UnsupportedCompression { file_name: String, method: u16 },
+    InvalidDeflateData(&'static str),
+    ChecksumMismatch(String),
+}
+
+impl std::fmt::Display for ZipError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZipError::NotAZipFile => write!(f, "Not a zip file"),
+            ZipError::Truncated => write!(f, "Zip file ended unexpectedly"),
+            ZipError::UnsupportedCompression { file_name, method } => write!(
+                f,
+                "{file_name} uses compression method {method}, only stored and deflated files are supported"
+            ),
+            ZipError::InvalidDeflateData(reason) => {
+                write!(f, "Invalid compressed data: {reason}")
+            }
+            ZipError::ChecksumMismatch(file_name) => {
+                write!(f, "{file_name} does not match its checksum in the zip file")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ZipError {}
+
+fn read_u16(bytes: &[u8], position: usize) -> Result<u16, ZipError> {
+    bytes
+        .get(position..position + 2)
+        .map(|field| u16::from_le_bytes(field.try_into().unwrap()))
+        .ok_or(ZipError::Truncated)
+}
+
+fn read_u32(bytes: &[u8], position: usize) -> Result<u32, ZipError> {
+    bytes
+        .get(position..position + 4)
+        .map(|field| u32::from_le_bytes(field.try_into().unwrap()))
+        .ok_or(ZipError::Truncated)
+}
+
+// Returns each file in the archive by its name, without any folders. Folders themselves are skipped.
+pub fn extract_files(archive: &[u8]) -> Result<HashMap<String, Vec<u8>>, ZipError> {
+    // The end of central directory record is followed by a comment of up to 64KiB
+    let end_position = (0..=archive.len().saturating_sub(END_OF_CENTRAL_DIRECTORY_SIZE))
+        .rev()
+        .take(0x10000)
+        .find(|position| read_u32(archive, *position) == Ok(END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+        .ok_or(ZipError::NotAZipFile)?;
+    let num_entries = read_u16(archive, end_position + 10)?;
+    let mut position = read_u32(archive, end_position + 16)? as usize;
+    let mut files = HashMap::new();
+
+    for _ in 0..num_entries {
+        if read_u32(archive, position)? != CENTRAL_DIRECTORY_SIGNATURE {
+            return Err(ZipError::NotAZipFile);
+        }
+        let method = read_u16(archive, position + 10)?;
+        let crc32 = read_u32(archive, position + 16)?;
+        let compressed_size = read_u32(archive, position + 20)? as usize;
+        let file_name_length = read_u16(archive, position + 28)? as usize;
+        let extra_field_length = read_u16(archive, position + 30)? as usize;
+        let comment_length = read_u16(archive, position + 32)? as usize;
+        let local_header_position = read_u32(archive, position + 42)? as usize;
+        let name_start = position + CENTRAL_DIRECTORY_HEADER_SIZE;
+        let path = archive
+            .get(name_start..name_start + file_name_length)
+            .ok_or(ZipError::Truncated)?;
+        let path = String::from_utf8_lossy(path).into_owned();
+        position = name_start + file_name_length + extra_field_length + comment_length;
+
+        if path.ends_with('/') {
+            continue;
+        }
+        let file_name = path.rsplit('/').next().unwrap().to_owned();
+
+        if read_u32(archive, local_header_position)? != LOCAL_HEADER_SIGNATURE {
+            return Err(ZipError::NotAZipFile);
+        }
+        let data_start = local_header_position
+            + LOCAL_HEADER_SIZE
+            + read_u16(archive, local_header_position + 26)? as usize
+            + read_u16(archive, local_header_position + 28)? as usize;
+        let data = archive
+            .get(data_start..data_start + compressed_size)
+            .ok_or(ZipError::Truncated)?;
+
+        let contents = match method {
+            STORED => data.to_vec(),
+            DEFLATED => inflate(data)?,
+            _ => return Err(ZipError::UnsupportedCompression { file_name, method }),
+        };
+        if checksum::crc32(&contents) != crc32 {
+            return Err(ZipError::ChecksumMismatch(file_name));
+        }
+
+        files.insert(file_name, contents);
+    }
+
+    Ok(files)
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    bit_buffer: u32,
+    num_buffered_bits: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, num_bits: u32) -> Result<u32, ZipError> {
+        while self.num_buffered_bits < num_bits {
+            let byte = *self
+                .bytes
+                .get(self.position)
+                .ok_or(ZipError::InvalidDeflateData("ran out of input"))?;
+            self.position += 1;
+            self.bit_buffer |= u32::from(byte) << self.num_buffered_bits;
+            self.num_buffered_bits += 8;
+        }
+
+        let value = self.bit_buffer & ((1 << num_bits) - 1);
+        self.bit_buffer >>= num_bits;
+        self.num_buffered_bits -= num_bits;
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.bit_buffer = 0;
+        self.num_buffered_bits = 0;
+    }
+}
+
+const MAX_CODE_LENGTH: usize = 15;
+
+// A canonical Huffman code, stored as the number of codes of each length and the symbols in code order
+struct Huffman {
+    length_counts: [u16; MAX_CODE_LENGTH + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn from_code_lengths(code_lengths: &[u8]) -> Self {
+        let mut length_counts = [0; MAX_CODE_LENGTH + 1];
+        for code_length in code_lengths {
+            length_counts[usize::from(*code_length)] += 1;
+        }
+        length_counts[0] = 0;
+
+        let mut symbols = Vec::new();
+        for code_length in 1..=MAX_CODE_LENGTH as u8 {
+            for (symbol, symbol_code_length) in code_lengths.iter().enumerate() {
+                if *symbol_code_length == code_length {
+                    symbols.push(symbol as u16);
+                }
+            }
+        }
+
+        Huffman {
+            length_counts,
+            symbols,
+        }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, ZipError> {
+        let mut code = 0;
+        let mut first_code = 0;
+        let mut index = 0;
+
+        for length_count in &self.length_counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(*length_count);
+            if code - first_code < count {
+                return Ok(self.symbols[(index + code - first_code) as usize]);
+            }
+            index += count;
+            first_code = (first_code + count) << 1;
+            code <<= 1;
+        }
+
+        Err(ZipError::InvalidDeflateData("invalid Huffman code"))
+    }
+}
+
+// From RFC 1951
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+const END_OF_BLOCK: u16 = 256;
+
+pub fn inflate(compressed: &[u8]) -> Result<Vec<u8>, ZipError> {
+    let mut reader = BitReader {
+        bytes: compressed,
+        position: 0,
+        bit_buffer: 0,
+        num_buffered_bits: 0,
+    };
+    let mut output = Vec::new();
+
+    loop {
+        let is_last_block = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => inflate_stored_block(&mut reader, &mut output)?,
+            1 => {
+                let (literal_lengths, distances) = fixed_codes();
+                inflate_compressed_block(&mut reader, &mut output, &literal_lengths, &distances)?;
+            }
+            2 => {
+                let (literal_lengths, distances) = read_dynamic_codes(&mut reader)?;
+                inflate_compressed_block(&mut reader, &mut output, &literal_lengths, &distances)?;
+            }
+            _ => return Err(ZipError::InvalidDeflateData("invalid block type")),
+        }
+
+        if is_last_block {
+            return Ok(output);
+        }
+    }
+}
+
+fn inflate_stored_block(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<(), ZipError> {
+    reader.align_to_byte();
+    let length = read_u16(reader.bytes, reader.position)
+        .map_err(|_| ZipError::InvalidDeflateData("ran out of input"))?;
+    let inverted_length = read_u16(reader.bytes, reader.position + 2)
+        .map_err(|_| ZipError::InvalidDeflateData("ran out of input"))?;
+    if length != !inverted_length {
+        return Err(ZipError::InvalidDeflateData("stored block length mismatch"));
+    }
+
+    let start = reader.position + 4;
+    let data = reader
+        .bytes
+        .get(start..start + usize::from(length))
+        .ok_or(ZipError::InvalidDeflateData("ran out of input"))?;
+    output.extend_from_slice(data);
+    reader.position = start + usize::from(length);
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut literal_length_code_lengths = [0; 288];
+    literal_length_code_lengths[..144].fill(8);
+    literal_length_code_lengths[144..256].fill(9);
+    literal_length_code_lengths[256..280].fill(7);
+    literal_length_code_lengths[280..].fill(8);
+
+    (
+        Huffman::from_code_lengths(&literal_length_code_lengths),
+        Huffman::from_code_lengths(&[5; 30]),
+    )
+}
+
+fn read_dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), ZipError> {
+    let num_literal_length_codes = reader.bits(5)? as usize + 257;
+    let num_distance_codes = reader.bits(5)? as usize + 1;
+    let num_code_length_codes = reader.bits(4)? as usize + 4;
+
+    let mut code_length_code_lengths = [0; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(num_code_length_codes) {
+        code_length_code_lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::from_code_lengths(&code_length_code_lengths);
+
+    let num_code_lengths = num_literal_length_codes + num_distance_codes;
+    let mut code_lengths: Vec<u8> = Vec::with_capacity(num_code_lengths);
+    while code_lengths.len() < num_code_lengths {
+        let (code_length, repeat_count) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous_code_length = *code_lengths.last().ok_or(
+                    ZipError::InvalidDeflateData("repeat with no previous length"),
+                )?;
+                (previous_code_length, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        code_lengths.extend(std::iter::repeat_n(code_length, repeat_count as usize));
+    }
+    if code_lengths.len() > num_code_lengths {
+        return Err(ZipError::InvalidDeflateData("too many code lengths"));
+    }
+
+    let (literal_length_code_lengths, distance_code_lengths) =
+        code_lengths.split_at(num_literal_length_codes);
+    Ok((
+        Huffman::from_code_lengths(literal_length_code_lengths),
+        Huffman::from_code_lengths(distance_code_lengths),
+    ))
+}
+
+fn inflate_compressed_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literal_lengths: &Huffman,
+    distances: &Huffman,
+) -> Result<(), ZipError> {
+    loop {
+        let symbol = literal_lengths.decode(reader)?;
+
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            END_OF_BLOCK => return Ok(()),
+            _ => {
+                let length_index = usize::from(symbol - 257);
+                let length = *LENGTH_BASES
+                    .get(length_index)
+                    .ok_or(ZipError::InvalidDeflateData("invalid length code"))?
+                    as usize
+                    + reader.bits(u32::from(LENGTH_EXTRA_BITS[length_index]))? as usize;
+
+                let distance_index = usize::from(distances.decode(reader)?);
+                let distance = *DISTANCE_BASES
+                    .get(distance_index)
+                    .ok_or(ZipError::InvalidDeflateData("invalid distance code"))?
+                    as usize
+                    + reader.bits(u32::from(DISTANCE_EXTRA_BITS[distance_index]))? as usize;
+                if distance > output.len() {
+                    return Err(ZipError::InvalidDeflateData("distance too far back"));
+                }
+
+                // The copy can overlap the bytes it's producing, so it has to go byte by byte
+                let copy_start = output.len() - distance;
+                for index in copy_start..copy_start + length {
+                    output.push(output[index]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Raw deflate data, as produced by zlib
+    const STORED_BLOCK: [u8; 11] = [
+        0x01, 0x06, 0x00, 0xF9, 0xFF, 0x73, 0x74, 0x6F, 0x72, 0x65, 0x64,
+    ];
+    const FIXED_CODES_BLOCK: [u8; 35] = [
+        0x73, 0x74, 0x0A, 0x72, 0x74, 0x76, 0x74, 0x71, 0x04, 0x52, 0x0A, 0x8E, 0x38, 0xD8, 0x89,
+        0x49, 0x45, 0x89, 0xC9, 0x89, 0x29, 0x20, 0x4A, 0x21, 0x31, 0x27, 0x31, 0x3B, 0xB1, 0x2A,
+        0x31, 0x97, 0x04, 0x41, 0x00,
+    ];
+    const DYNAMIC_CODES_BLOCK: [u8; 19] = [
+        0x05, 0xC1, 0x01, 0x01, 0x00, 0x00, 0x00, 0x01, 0xA0, 0xAD, 0xE1, 0xFF, 0x06, 0x05, 0x00,
+        0x00, 0x00, 0xA4, 0x3B,
+    ];
+
+    fn zip_archive(entries: &[(&str, u16, &[u8], &[u8])]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut central_directory = Vec::new();
+
+        for (path, method, data, contents) in entries {
+            let mut header = Vec::new();
+            header.extend_from_slice(&[0x14, 0x00, 0x00, 0x00]);
+            header.extend_from_slice(&method.to_le_bytes());
+            header.extend_from_slice(&[0x00; 4]);
+            header.extend_from_slice(&checksum::crc32(contents).to_le_bytes());
+            header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(path.len() as u16).to_le_bytes());
+            header.extend_from_slice(&[0x00; 2]);
+
+            central_directory.extend_from_slice(&CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+            central_directory.extend_from_slice(&[0x14, 0x00]);
+            central_directory.extend_from_slice(&header);
+            central_directory.extend_from_slice(&[0x00; 10]);
+            central_directory.extend_from_slice(&(archive.len() as u32).to_le_bytes());
+            central_directory.extend_from_slice(path.as_bytes());
+
+            archive.extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+            archive.extend_from_slice(&header);
+            archive.extend_from_slice(path.as_bytes());
+            archive.extend_from_slice(data);
+        }
+
+        let central_directory_position = archive.len() as u32;
+        archive.extend_from_slice(&central_directory);
+        archive.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        archive.extend_from_slice(&[0x00; 4]);
+        archive.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&(central_directory.len() as u32).to_le_bytes());
+        archive.extend_from_slice(&central_directory_position.to_le_bytes());
+        archive.extend_from_slice(&[0x00; 2]);
+        archive
+    }
+
+    #[test]
+    fn inflate_decodes_every_block_type() {
+        assert_eq!(inflate(&STORED_BLOCK).unwrap(), b"stored");
+        assert_eq!(
+            inflate(&FIXED_CODES_BLOCK).unwrap(),
+            b"ABRACADABRA ABRACADABRA ABRACADABRA abracadabra alakazam abracadabra alakazam abracadabra alakazam "
+        );
+        assert_eq!(
+            inflate(&DYNAMIC_CODES_BLOCK).unwrap(),
+            [b"a".repeat(30), b"bcd".to_vec()].concat()
+        );
+        assert_eq!(
+            inflate(&FIXED_CODES_BLOCK[..20]),
+            Err(ZipError::InvalidDeflateData("ran out of input"))
+        );
+    }
+
+    #[test]
+    fn extract_files_reads_stored_and_deflated_files_without_their_folders() {
+        let archive = zip_archive(&[
+            ("roms/", STORED, &[], &[]),
+            (
+                "roms/a.bin",
+                STORED,
+                &[0x01, 0x02, 0x03],
+                &[0x01, 0x02, 0x03],
+            ),
+            ("b.bin", DEFLATED, &STORED_BLOCK, b"stored"),
+        ]);
+
+        let files = extract_files(&archive).unwrap();
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files["a.bin"], vec![0x01, 0x02, 0x03]);
+        assert_eq!(files["b.bin"], b"stored");
+    }
+
+    #[test]
+    fn extract_files_rejects_bad_archives() {
+        assert_eq!(extract_files(b"not a zip"), Err(ZipError::NotAZipFile));
+
+        let archive = zip_archive(&[("a.bin", STORED, &[0x01], &[0x02])]);
+        assert_eq!(
+            extract_files(&archive),
+            Err(ZipError::ChecksumMismatch("a.bin".to_owned()))
+        );
+
+        let archive = zip_archive(&[("a.bin", 12, &[0x01], &[0x01])]);
+        assert_eq!(
+            extract_files(&archive),
+            Err(ZipError::UnsupportedCompression {
+                file_name: "a.bin".to_owned(),
+                method: 12
+            })
+        );
+    }
+}
@@ -11,7 +11,7 @@ fn run_next_operation(state: &mut State) -> bool {
     state.run_operation(&operation);
 
     if is_out_operation {
-        let port_number = state.memory[(state.program_counter - 1) as usize];
+        let port_number = state.memory.read(state.program_counter - 1);
         match port_number {
             0 => return true,
             1 => print_test_output(state),
@@ -35,7 +35,7 @@ fn print_test_output(state: &State) {
             let mut memory_character: char;
 
             loop {
-                memory_character = state.memory[memory_address as usize] as char;
+                memory_character = state.memory.read(memory_address) as char;
                 if memory_character == '$' {
                     break;
                 } else {